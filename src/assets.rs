@@ -1,197 +1,813 @@
+// Images, sounds, fonts, sprite sheets, animations and state machines, loaded by id from `resources/assets.toml`
 use crate::prelude::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Read;
+use std::path;
+use ggez::{filesystem, GameError};
+use ggez::graphics::Rect;
 
-type Anim = (Vec<Sprite>, u8);      // (frames, fps)
+pub const ASSETS_PATH: &str = "/assets.toml";
+// Each theme is a directory in here mirroring `resources/`, with its own `assets.toml`
+pub const THEMES_DIR: &str = "/themes";
+pub const DEFAULT_THEME: &str = "default";
+// Ids of the animations and state machine the game's entities are built from
+const REQUIRED_ANIMS: [&str; 3] = ["ptero_anim", "rock_anim", "tumbleweed_anim"];
+const DINO_STATE_MACHINE: &str = "dino_state_machine";
+// Drawn in place of images that couldn't be loaded
+const PLACEHOLDER_SIZE: u16 = 16;
+
+
+// Handle to an asset, made by hashing its id from the manifest.
+// The hash is a const fn, so code can name the assets it needs as constants.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AssetTag(u64);
+
+impl AssetTag{
+    // FNV-1a
+    pub const fn new(id: &str) -> AssetTag {
+        let bytes = id.as_bytes();
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+            i += 1;
+        }
+        AssetTag(hash)
+    }
+
+    // Assets the code refers to directly
+    pub const DINO_ANIM_RUN:      AssetTag = AssetTag::new("dino.run");
+    pub const DINO_STATE_MACHINE: AssetTag = AssetTag::new("dino_state_machine");
+    pub const CACTUS_SMALL_1:     AssetTag = AssetTag::new("cactus_small_1");
+    pub const CACTUS_SMALL_2:     AssetTag = AssetTag::new("cactus_small_2");
+    pub const CACTUS_SMALL_3:     AssetTag = AssetTag::new("cactus_small_3");
+    pub const CACTUS_BIG_1:       AssetTag = AssetTag::new("cactus_big_1");
+    pub const CACTUS_BIG_2:       AssetTag = AssetTag::new("cactus_big_2");
+    pub const CACTUS_BIG_3:       AssetTag = AssetTag::new("cactus_big_3");
+    pub const GROUND_1:           AssetTag = AssetTag::new("ground_1");
+    pub const GROUND_2:           AssetTag = AssetTag::new("ground_2");
+    pub const PTERO_1:            AssetTag = AssetTag::new("ptero_1");
+    pub const PTERO_ANIM:         AssetTag = AssetTag::new("ptero_anim");
+    pub const TUMBLEWEED_1:       AssetTag = AssetTag::new("tumbleweed_1");
+    pub const TUMBLEWEED_ANIM:    AssetTag = AssetTag::new("tumbleweed_anim");
+    pub const ROCK_1:             AssetTag = AssetTag::new("rock_1");
+    pub const ROCK_ANIM:          AssetTag = AssetTag::new("rock_anim");
+    pub const PICKUP_COIN:        AssetTag = AssetTag::new("pickup_coin");
+    pub const PICKUP_SHIELD:      AssetTag = AssetTag::new("pickup_shield");
+    pub const PICKUP_SLOW_MOTION: AssetTag = AssetTag::new("pickup_slow_motion");
+    pub const PICKUP_DOUBLE_JUMP: AssetTag = AssetTag::new("pickup_double_jump");
+    pub const PICKUP_MAGNET:      AssetTag = AssetTag::new("pickup_magnet");
+    pub const MOON:               AssetTag = AssetTag::new("moon");
+    pub const STAR:               AssetTag = AssetTag::new("star");
+    pub const RESTART_BUTTON:     AssetTag = AssetTag::new("restart_button");
+    pub const HEART:              AssetTag = AssetTag::new("heart");
+    pub const JUMP_SOUND:         AssetTag = AssetTag::new("jump_sound");
+    pub const DEATH_SOUND:        AssetTag = AssetTag::new("death_sound");
+    pub const POINT_SOUND:        AssetTag = AssetTag::new("point_sound");
+    pub const COIN_SOUND:         AssetTag = AssetTag::new("coin_sound");
+    pub const POWER_UP_SOUND:     AssetTag = AssetTag::new("power_up_sound");
+    pub const SHIELD_BREAK_SOUND: AssetTag = AssetTag::new("shield_break_sound");
+    pub const HURT_SOUND:         AssetTag = AssetTag::new("hurt_sound");
+    pub const FOOTSTEP_SOUND:     AssetTag = AssetTag::new("footstep_sound");
+
+    pub fn cactus_tags() -> Vec<AssetTag> {
+        vec![
+            AssetTag::CACTUS_SMALL_1,
+            AssetTag::CACTUS_SMALL_2,
+            AssetTag::CACTUS_SMALL_3,
+            AssetTag::CACTUS_BIG_1,
+            AssetTag::CACTUS_BIG_2,
+            AssetTag::CACTUS_BIG_3,
+        ]
+    }
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    // The manifest is missing or couldn't be parsed
+    Manifest { path: String, cause: String },
+    // A file listed in the manifest couldn't be loaded
+    File { id: String, path: String, cause: GameError },
+    // An id refers to something the manifest doesn't define
+    Undefined { id: String, wanted_by: String },
+    // An animation without frames
+    Empty { id: String },
+    // Something in the manifest that doesn't make sense
+    Invalid { id: String, cause: String },
+    // An asset the code uses that didn't load
+    Missing { id: String, wanted_by: String },
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Manifest { path, cause }    => write!(f, "Failed to read asset manifest {path}: {cause}"),
+            AssetError::File { id, path, cause }    => write!(f, "Failed to load asset {id} from {path}: {cause}"),
+            AssetError::Undefined { id, wanted_by } => write!(f, "{wanted_by} refers to asset {id}, which isn't in the manifest"),
+            AssetError::Empty { id }                => write!(f, "Animation {id} has no frames"),
+            AssetError::Invalid { id, cause }       => write!(f, "Asset {id} is invalid: {cause}"),
+            AssetError::Missing { id, wanted_by }   => write!(f, "{wanted_by} needs asset {id}, which didn't load"),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+impl From<AssetError> for GameError {
+    fn from(e: AssetError) -> GameError {
+        GameError::ResourceLoadError(e.to_string())
+    }
+}
+
+// A sound that may have failed to load, like when there's no audio device. Those play as silence.
+pub enum Sound {
+    Loaded(audio::Source),
+    Silent,
+}
+
+impl Sound {
+    pub fn play(&mut self, ctx: &Context) -> GameResult {
+        match self {
+            Sound::Loaded(source) => source.play(ctx),
+            Sound::Silent         => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct FrameEventDef {
+    pub frame: usize,
+    pub name: String,
+}
+
+// How an animation plays, besides its frames
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Playback {
+    // Sheets set this from the tag's direction and repeat count
+    pub loop_mode: Option<LoopMode>,
+    // Events fired when playback reaches a frame
+    pub events: Vec<FrameEventDef>,
+    // Played faster as the game scrolls faster
+    pub scale_with_scroll: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AnimDef {
+    // Image ids
+    pub frames: Vec<String>,
+    pub fps: u8,
+    #[serde(flatten)]
+    pub playback: Playback,
+}
+
+pub struct AnimData {
+    pub frames: Vec<Sprite>,
+    // Seconds each frame shows for
+    pub durations: Vec<f32>,
+    pub loop_mode: LoopMode,
+    pub events: Vec<(usize, AnimEvent)>,
+    pub scale_with_scroll: bool,
+}
+
+impl AnimData {
+    fn new(frames: Vec<Sprite>, durations: Vec<f32>, loop_mode: LoopMode, playback: Option<&Playback>) -> AnimData {
+        let playback = playback.cloned().unwrap_or_default();
+        AnimData {
+            frames,
+            durations,
+            loop_mode: playback.loop_mode.unwrap_or(loop_mode),
+            events: playback.events.iter().map(|event| (event.frame, AnimEvent::new(&event.name))).collect(),
+            scale_with_scroll: playback.scale_with_scroll,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AssetManifest {
+    // Font id the UI is drawn with
+    pub ui_font: String,
+    // File paths by id
+    pub images: BTreeMap<String, String>,
+    pub sounds: BTreeMap<String, String>,
+    pub fonts: BTreeMap<String, String>,
+    // Aseprite JSON exports. Each tag in a sheet becomes an animation "{sheet}.{tag}", and the
+    // sheet itself a state machine with a state per tag. A sheet without tags is one animation.
+    pub sheets: BTreeMap<String, String>,
+    pub animations: BTreeMap<String, AnimDef>,
+    // Playback settings for animations from sheets, by animation id
+    pub playback: BTreeMap<String, Playback>,
+    // States, their animations and the transitions between them. A sheet's own
+    // state machine is replaced by one here with the same id.
+    pub state_machines: BTreeMap<String, StateMachineDef>,
+}
+
+pub fn theme_dir(theme: &str) -> String {
+    format!("{THEMES_DIR}/{theme}")
+}
+
+fn theme_manifest_path(theme: &str) -> String {
+    format!("{}{ASSETS_PATH}", theme_dir(theme))
+}
+
+// Names of the themes in `resources/themes`, sorted
+pub fn themes(ctx: &mut Context) -> Vec<String> {
+    let mut themes: Vec<String> = match filesystem::read_dir(ctx, THEMES_DIR) {
+        Ok(dirs) => dirs
+            .filter(|dir| filesystem::is_dir(ctx, dir))
+            .filter_map(|dir| Some(dir.file_name()?.to_string_lossy().into_owned()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    themes.sort();
+    themes.dedup();
+    themes
+}
+
+impl AssetManifest {
+    pub fn from_toml(src: &str) -> Result<AssetManifest, toml::de::Error> {
+        toml::from_str(src)
+    }
+
+    pub fn load(ctx: &mut Context) -> Result<AssetManifest, AssetError> {
+        AssetManifest::read(ctx, ASSETS_PATH)
+    }
+    fn read(ctx: &mut Context, file_path: &str) -> Result<AssetManifest, AssetError> {
+        let error = |cause: String| AssetError::Manifest { path: file_path.to_string(), cause };
+        let mut buffer = String::new();
+        let mut file = filesystem::open(ctx, path::Path::new(file_path)).map_err(|e| error(e.to_string()))?;
+        file.read_to_string(&mut buffer).map_err(|e| error(e.to_string()))?;
+        AssetManifest::from_toml(&buffer).map_err(|e| error(e.to_string()))
+    }
+    // The default manifest with the theme laid over it
+    pub fn load_themed(ctx: &mut Context, theme: &str) -> Result<AssetManifest, AssetError> {
+        let manifest = AssetManifest::load(ctx)?;
+        if theme == DEFAULT_THEME {return Ok(manifest)}
+        let dir = theme_dir(theme);
+        if !filesystem::is_dir(ctx, &dir) {
+            return Err(AssetError::Manifest { path: dir, cause: "no such theme".to_string() });
+        }
+        let theme_manifest = theme_manifest_path(theme);
+        let overlay =
+            if filesystem::is_file(ctx, &theme_manifest) {AssetManifest::read(ctx, &theme_manifest)?}
+            else {AssetManifest::default()};
+        Ok(manifest.with_theme(&overlay, &dir, |file| filesystem::is_file(ctx, file)))
+    }
+
+    // Ids the theme lists replace the default ones, with their paths taken from the theme's
+    // directory. Files the theme has at the same path as a default one replace it too.
+    pub fn with_theme(&self, theme: &AssetManifest, dir: &str, has_file: impl Fn(&str) -> bool) -> AssetManifest {
+        let resolve = |files: &BTreeMap<String, String>, overrides: &BTreeMap<String, String>| -> BTreeMap<String, String> {
+            let mut files: BTreeMap<String, String> = files.iter().map(|(id, file)| {
+                let themed = format!("{dir}{file}");
+                (id.clone(), if has_file(&themed) {themed} else {file.clone()})
+            }).collect();
+            for (id, file) in overrides.iter() {
+                files.insert(id.clone(), format!("{dir}{file}"));
+            }
+            files
+        };
+        let mut manifest = AssetManifest {
+            ui_font: if theme.ui_font.is_empty() {self.ui_font.clone()} else {theme.ui_font.clone()},
+            images: resolve(&self.images, &theme.images),
+            sounds: resolve(&self.sounds, &theme.sounds),
+            fonts: resolve(&self.fonts, &theme.fonts),
+            sheets: resolve(&self.sheets, &theme.sheets),
+            animations: self.animations.clone(),
+            playback: self.playback.clone(),
+            state_machines: self.state_machines.clone(),
+        };
+        manifest.animations.extend(theme.animations.clone());
+        manifest.playback.extend(theme.playback.clone());
+        manifest.state_machines.extend(theme.state_machines.clone());
+        manifest
+    }
+
+    fn has_file(&self, file: &str) -> bool {
+        self.images.values().chain(self.sounds.values()).chain(self.fonts.values()).chain(self.sheets.values()).any(|f| f == file)
+    }
+
+    // Catches the mistakes that would otherwise crash the game later. Frames that aren't
+    // in [images] are let through and drawn as the placeholder. Animations from sheets
+    // aren't known until the sheet is read, so any id starting with a sheet's is let through too.
+    pub fn check(&self) -> Result<(), AssetError> {
+        for (id, anim) in self.animations.iter() {
+            if anim.frames.is_empty() {
+                return Err(AssetError::Empty { id: id.clone() });
+            }
+        }
+        let is_anim = |anim: &String| {
+            self.animations.contains_key(anim) || self.sheets.keys().any(|sheet| anim == sheet || anim.starts_with(&format!("{sheet}.")))
+        };
+        for (id, def) in self.state_machines.iter() {
+            if let Some(anim) = def.states.values().find(|anim| !is_anim(anim)) {
+                return Err(AssetError::Undefined { id: anim.clone(), wanted_by: id.clone() });
+            }
+            StateMachineData::from_def(id, def)?;
+        }
+        if let Some(anim) = self.playback.keys().find(|anim| !is_anim(anim)) {
+            return Err(AssetError::Undefined { id: anim.clone(), wanted_by: "[playback]".to_string() });
+        }
+        Ok(())
+    }
+}
 
 pub struct Assets{
-    pub dino_run_l:     Image,
-    pub dino_run_r:     Image,
-    pub dino_dead:      Image,
-    pub dino_idle:      Image,
-    pub cactus_small_1: Image,
-    pub cactus_small_2: Image,
-    pub cactus_small_3: Image,
-    pub cactus_big_1:   Image,
-    pub cactus_big_2:   Image,
-    pub cactus_big_3:   Image,
-    pub cloud:          Image,
-    pub ground_1:       Image,
-    pub ground_2:       Image,
-    pub ptero_1:        Image,
-    pub ptero_2:        Image,
-    pub restart_button: Image,
-    pub dino_anim_run:  Anim,
-    pub dino_anim_idle: Anim,
-    pub dino_anim_dead: Anim,
-    pub ptero_anim:     Anim,
-    pub font:           graphics::Font,
-    pub jump_sound:     audio::Source,
-    pub death_sound:    audio::Source,
-    pub point_sound:    audio::Source,
+    theme:          String,
+    // Off when the game runs without an audio device, and every sound is silent
+    audio:          bool,
+    manifest:       AssetManifest,
+    images:         HashMap<AssetTag, Image>,
+    // Width, height and RGBA pixels of each image, to pack the atlas from
+    pixels:         HashMap<AssetTag, (u32, u32, Vec<u8>)>,
+    atlas:          Option<Atlas>,
+    sounds:         HashMap<AssetTag, Sound>,
+    fonts:          HashMap<AssetTag, graphics::Font>,
+    anims:          HashMap<AssetTag, AnimData>,
+    state_machines: HashMap<AssetTag, StateMachineData>,
+    // Image each sheet was cut from, by sheet id
+    sheet_images:   HashMap<String, String>,
+    // Slices on each frame, by frame image and slice name
+    slices:         HashMap<AssetTag, HashMap<String, Rect>>,
+    // Id behind every handle, to catch two ids that hash the same
+    names:          HashMap<AssetTag, String>,
+    placeholder:    Image,
+    // Handed out for sounds that aren't in the manifest
+    silent:         Sound,
+    pub font:       graphics::Font,
 }
 
 impl Assets{
-    pub fn new(ctx: &mut Context) -> Box<Assets> {
-        // DINO
-        let dino_run_l = Image::new(ctx, "/images/dino_run_l.png").unwrap();
-        let dino_run_r = Image::new(ctx, "/images/dino_run_r.png").unwrap();
-        let dino_anim_run = (vec![
-            Sprite::new(AssetTag::DinoRunL),
-            Sprite::new(AssetTag::DinoRunR),
-        ], 8);
-        let dino_anim_idle = (vec![
-            Sprite::new(AssetTag::DinoIdle),
-        ], 1);
-        let dino_anim_dead = (vec![
-            Sprite::new(AssetTag::DinoDead),
-        ], 1);
-
-        // PTERO
-        let ptero_1 = Image::new(ctx, "/images/ptero_1.png"  ).unwrap();
-        let ptero_2 = Image::new(ctx, "/images/ptero_2.png"  ).unwrap();
-        let ptero_anim = (vec![
-            Sprite::new(AssetTag::Ptero1),
-            Sprite::new(AssetTag::Ptero2),
-        ], 4);
-
-        // OTHER
-        let font = graphics::Font::new(ctx, "/fonts/PressStart2P-Regular.ttf").unwrap();
-        Box::new(
-            Assets{
-                dino_run_l,
-                dino_run_r,
-                dino_dead:      Image::new(ctx, "/images/dino_dead.png" ).unwrap(),
-                dino_idle:      Image::new(ctx, "/images/dino_idle.png" ).unwrap(),
-                cactus_small_1: Image::new(ctx, "/images/cactus_small_1.png"  ).unwrap(),
-                cactus_small_2: Image::new(ctx, "/images/cactus_small_2.png"  ).unwrap(),
-                cactus_small_3: Image::new(ctx, "/images/cactus_small_3.png"  ).unwrap(),
-                cactus_big_1:   Image::new(ctx, "/images/cactus_big_1.png"  ).unwrap(),
-                cactus_big_2:   Image::new(ctx, "/images/cactus_big_2.png"  ).unwrap(),
-                cactus_big_3:   Image::new(ctx, "/images/cactus_big_3.png"  ).unwrap(),
-                cloud:          Image::new(ctx, "/images/cloud.png"     ).unwrap(),
-                ground_1:       Image::new(ctx, "/images/ground_1.png"  ).unwrap(),
-                ground_2:       Image::new(ctx, "/images/ground_2.png"  ).unwrap(),
-                ptero_1,
-                ptero_2,
-                restart_button: Image::new(ctx, "/images/restart_button.png"  ).unwrap(),
-                dino_anim_run,
-                dino_anim_idle,
-                dino_anim_dead,
-                ptero_anim,
-                font,
-                jump_sound:     audio::Source::new(ctx, "/sounds/jump.wav").unwrap(),
-                death_sound:    audio::Source::new(ctx, "/sounds/death.wav").unwrap(),
-                point_sound:    audio::Source::new(ctx, "/sounds/point.wav").unwrap(),
+    pub fn new(ctx: &mut Context, audio: bool) -> Result<Box<Assets>, AssetError> {
+        Assets::with_theme(ctx, DEFAULT_THEME, audio)
+    }
+    // Assets the theme doesn't have come from the default one. Fails if an animation
+    // the game's entities use didn't load, like when the theme's sheet is broken.
+    pub fn with_theme(ctx: &mut Context, theme: &str, audio: bool) -> Result<Box<Assets>, AssetError> {
+        let manifest = AssetManifest::load_themed(ctx, theme)?;
+        let mut assets = Assets::from_manifest(ctx, manifest, audio)?;
+        assets.theme = theme.to_string();
+        assets.check_required()?;
+        Ok(assets)
+    }
+    fn check_required(&self) -> Result<(), AssetError> {
+        let game = "The game".to_string();
+        let mut anims: Vec<(String, String)> = REQUIRED_ANIMS.iter().map(|id| (id.to_string(), game.clone())).collect();
+        // Machines cut from a sheet only have states for the animations that loaded
+        match self.manifest.state_machines.get(DINO_STATE_MACHINE) {
+            Some(def) => anims.extend(def.states.values().map(|anim| (anim.clone(), DINO_STATE_MACHINE.to_string()))),
+            None if self.state_machines.contains_key(&AssetTag::DINO_STATE_MACHINE) => (),
+            None => return Err(AssetError::Missing { id: DINO_STATE_MACHINE.to_string(), wanted_by: game }),
+        }
+        match anims.into_iter().find(|(id, _)| !self.anims.contains_key(&AssetTag::new(id))) {
+            Some((id, wanted_by)) => Err(AssetError::Missing { id, wanted_by }),
+            None => Ok(()),
+        }
+    }
+    // Files that fail to load are reported and replaced by the placeholder image, a silent sound or the default font
+    pub fn from_manifest(ctx: &mut Context, manifest: AssetManifest, audio: bool) -> Result<Box<Assets>, AssetError> {
+        manifest.check()?;
+        let placeholder = Assets::placeholder(ctx).map_err(|cause| AssetError::File {
+            id: "placeholder".to_string(),
+            path: String::new(),
+            cause,
+        })?;
+        let mut assets = Assets{
+            theme:          DEFAULT_THEME.to_string(),
+            audio,
+            manifest:       manifest.clone(),
+            images:         HashMap::new(),
+            pixels:         HashMap::new(),
+            atlas:          None,
+            sounds:         HashMap::new(),
+            fonts:          HashMap::new(),
+            anims:          HashMap::new(),
+            state_machines: HashMap::new(),
+            sheet_images:   HashMap::new(),
+            slices:         HashMap::new(),
+            names:          HashMap::new(),
+            placeholder,
+            silent:         Sound::Silent,
+            font:           graphics::Font::default(),
+        };
+        for (id, file) in manifest.images.iter() {
+            assets.load_image(ctx, id, file)?;
+        }
+        for (id, file) in manifest.sounds.iter() {
+            assets.load_sound(ctx, id, file)?;
+        }
+        for (id, file) in manifest.fonts.iter() {
+            assets.load_font(ctx, id, file)?;
+        }
+        for (id, file) in manifest.sheets.iter() {
+            assets.load_sheet(ctx, id, file)?;
+        }
+        for (id, anim) in manifest.animations.iter() {
+            let tag = assets.intern(id)?;
+            let frames = anim.frames.iter().map(|frame| Sprite::new(AssetTag::new(frame))).collect();
+            let durations = vec![1.0 / anim.fps.max(1) as f32; anim.frames.len()];
+            assets.anims.insert(tag, AnimData::new(frames, durations, LoopMode::Loop, Some(&anim.playback)));
+        }
+        for (id, def) in manifest.state_machines.iter() {
+            let tag = assets.intern(id)?;
+            assets.state_machines.insert(tag, StateMachineData::from_def(id, def)?);
+        }
+        assets.pick_ui_font();
+        assets.pack_atlas(ctx);
+        Ok(Box::new(assets))
+    }
+
+    // Reloads whatever uses the changed files, given as paths like "/images/cloud.png".
+    // Handles stay the same, so live components pick up the new data. A file that
+    // fails to load keeps its old version, and a broken manifest leaves everything as it was.
+    pub fn reload(&mut self, ctx: &mut Context, changed: &[String]) -> Result<(), AssetError> {
+        // A changed manifest, or a new file in the theme, can point ids at other files
+        let theme_dir = theme_dir(&self.theme);
+        let resolve_again = changed.iter().any(|file| {
+            file == ASSETS_PATH || (file.starts_with(&theme_dir) && !self.manifest.has_file(file))
+        });
+        if resolve_again {
+            let old = std::mem::replace(self, *Assets::with_theme(ctx, &self.theme.clone(), self.audio)?);
+            self.keep_missing(ctx, old);
+            return Ok(());
+        }
+        let manifest = self.manifest.clone();
+        let mut images_changed = false;
+        for (id, file) in manifest.images.iter().filter(|(_, file)| changed.contains(file)) {
+            self.load_image(ctx, id, file)?;
+            images_changed = true;
+        }
+        for (id, file) in manifest.sheets.iter() {
+            if changed.contains(file) || self.sheet_images.get(id).is_some_and(|image| changed.contains(image)) {
+                self.load_sheet(ctx, id, file)?;
+                images_changed = true;
             }
-        )
-    }
-    pub fn get_image(&self, tag: AssetTag) -> Option<&Image> {
-        match tag{
-            AssetTag::DinoRunL      => Some(&self.dino_run_l),
-            AssetTag::DinoRunR      => Some(&self.dino_run_r),
-            AssetTag::DinoDead      => Some(&self.dino_dead),
-            AssetTag::DinoIdle      => Some(&self.dino_idle),
-            AssetTag::Ground1       => Some(&self.ground_1),
-            AssetTag::Ground2       => Some(&self.ground_2),
-            AssetTag::Cloud         => Some(&self.cloud),
-            AssetTag::CactusSmall1  => Some(&self.cactus_small_1),
-            AssetTag::CactusSmall2  => Some(&self.cactus_small_2),
-            AssetTag::CactusSmall3  => Some(&self.cactus_small_3),
-            AssetTag::CactusBig1    => Some(&self.cactus_big_1),
-            AssetTag::CactusBig2    => Some(&self.cactus_big_2),
-            AssetTag::CactusBig3    => Some(&self.cactus_big_3),
-            AssetTag::Ptero1        => Some(&self.ptero_1),
-            AssetTag::Ptero2        => Some(&self.ptero_2),
-            AssetTag::RestartButton => Some(&self.restart_button),
-            _ => None
-        }
-    }
-    pub fn get_audio(&self, tag: AssetTag) -> Option<&audio::Source> {
-        match tag {
-            AssetTag::JumpSound     => Some(&self.jump_sound),
-            AssetTag::DeathSound    => Some(&self.death_sound),
-            AssetTag::PointSound    => Some(&self.point_sound),
-            _ => None
-        }
-    }
-    pub fn get_audio_mut(&mut self, tag: AssetTag) -> Option<&mut audio::Source> {
-        match tag {
-            AssetTag::JumpSound     => Some(&mut self.jump_sound),
-            AssetTag::DeathSound    => Some(&mut self.death_sound),
-            AssetTag::PointSound    => Some(&mut self.point_sound),
-            _ => None
-        }
-    }
-    fn dino_state_machine(&self, state: DinoState) -> AssetTag{
-        match state {
-            DinoState::Run  => AssetTag::DinoAnimRun,
-            DinoState::Jump => AssetTag::DinoAnimJump,
-            DinoState::Dead => AssetTag::DinoAnimDead,
-        }
-    }
-    pub fn get_anim(&self, tag: AssetTag) -> Option<&Anim> {
-        match tag {
-            AssetTag::DinoAnimRun   => Some(&self.dino_anim_run),
-            AssetTag::DinoAnimJump  => Some(&self.dino_anim_idle),
-            AssetTag::DinoAnimDead  => Some(&self.dino_anim_dead),
-            AssetTag::PteroAnim     => Some(&self.ptero_anim),
-            _ => None
         }
+        if images_changed {
+            self.pack_atlas(ctx);
+        }
+        for (id, file) in manifest.sounds.iter().filter(|(_, file)| changed.contains(file)) {
+            self.load_sound(ctx, id, file)?;
+        }
+        for (id, file) in manifest.fonts.iter().filter(|(_, file)| changed.contains(file)) {
+            self.load_font(ctx, id, file)?;
+        }
+        self.pick_ui_font();
+        Ok(())
     }
-    pub fn get_anim_frame(&self, tag: AssetTag, frame: usize) -> Option<Sprite> {
-        if let Some(anim) = self.get_anim(tag) {
-            return Some(anim.0[frame]);
+
+    // Live components may still use animations and state machines the reloaded manifest lost,
+    // or that failed to load this time. Those keep their old data, frames included.
+    fn keep_missing(&mut self, ctx: &mut Context, old: Assets) {
+        let mut images_kept = false;
+        for (tag, anim) in old.anims.into_iter() {
+            if self.anims.contains_key(&tag) {continue}
+            for frame in anim.frames.iter().map(|frame| frame.tag()) {
+                if self.images.contains_key(&frame) {continue}
+                let (Some(image), Some(pixels)) = (old.images.get(&frame), old.pixels.get(&frame)) else {continue};
+                self.images.insert(frame, image.clone());
+                self.pixels.insert(frame, pixels.clone());
+                images_kept = true;
+            }
+            self.anims.insert(tag, anim);
+        }
+        for (tag, machine) in old.state_machines.into_iter() {
+            self.state_machines.entry(tag).or_insert(machine);
+        }
+        for (tag, name) in old.names.into_iter() {
+            self.names.entry(tag).or_insert(name);
+        }
+        if images_kept {
+            self.pack_atlas(ctx);
         }
-        None
     }
-    pub fn get_anim_fps(&self, tag: AssetTag) -> Option<u8> {
-        if let Some(anim) = self.get_anim(tag) {
-            return Some(anim.1);
+    fn report(id: &str, path: &str, cause: GameError) {
+        eprintln!("{}", AssetError::File { id: id.to_string(), path: path.to_string(), cause });
+    }
+    fn load_image(&mut self, ctx: &mut Context, id: &str, file: &str) -> Result<(), AssetError> {
+        let tag = self.intern(id)?;
+        let result = Assets::decode_image(ctx, file).and_then(|(width, height, pixels)| self.add_image(ctx, tag, width, height, pixels));
+        if let Err(e) = result {
+            Assets::report(id, file, e);
         }
-        None
+        Ok(())
     }
-    pub fn get_anim_length(&self, tag: AssetTag) -> Option<usize> {
-        if let Some(anim) = self.get_anim(tag) {
-            return Some(anim.0.len());
-        }
-        None
-    }
-    pub fn get_state_machine_anim<State: 'static>(&self, tag: AssetTag, state: State) -> Option<AssetTag> {
-        match tag{
-            AssetTag::DinoStateMachine => {
-                if let Some(state) = (&state as &dyn std::any::Any).downcast_ref::<DinoState>(){
-                    Some(self.dino_state_machine(*state))
-                }
-                else {None}
+    fn add_image(&mut self, ctx: &mut Context, tag: AssetTag, width: u32, height: u32, pixels: Vec<u8>) -> GameResult {
+        let image = Image::from_rgba8(ctx, width as u16, height as u16, &pixels)?;
+        self.images.insert(tag, image);
+        self.pixels.insert(tag, (width, height, pixels));
+        Ok(())
+    }
+    // Each frame becomes an image "{sheet}#{index}", so sheets end up in the atlas like any other image
+    // A sheet that fails to load is reported, ids clashing with others fail the whole load
+    fn load_sheet(&mut self, ctx: &mut Context, id: &str, file: &str) -> Result<(), AssetError> {
+        match self.read_sheet(ctx, id, file) {
+            Err(e @ AssetError::File { .. }) => {
+                eprintln!("{e}");
+                Ok(())
             }
-            _ => None
+            result => result,
         }
     }
-}
+    fn read_sheet(&mut self, ctx: &mut Context, id: &str, file: &str) -> Result<(), AssetError> {
+        let error = |cause: GameError| AssetError::File { id: id.to_string(), path: file.to_string(), cause };
+        let mut buffer = String::new();
+        filesystem::open(ctx, file).and_then(|mut f| Ok(f.read_to_string(&mut buffer)?)).map_err(error)?;
+        let sheet = SpriteSheet::from_json(&buffer).map_err(|e| error(GameError::ResourceLoadError(e.to_string())))?;
+        let image_file = sheet.image_path(file);
+        self.sheet_images.insert(id.to_string(), image_file.clone());
+        let (width, height, pixels) = Assets::decode_image(ctx, &image_file).map_err(error)?;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum AssetTag{
-    DinoRunL, DinoRunR, DinoDead, DinoIdle,
-    DinoAnimRun, DinoAnimJump, DinoAnimDead,
-    DinoStateMachine,
-    CactusSmall1, CactusSmall2, CactusSmall3,
-    CactusBig1, CactusBig2, CactusBig3,
-    Ground1, Ground2,
-    Ptero1, Ptero2,
-    PteroAnim,
-    Cloud,
-    RestartButton,
-    JumpSound, DeathSound, PointSound,
+        let frame_id = |index: usize| format!("{id}#{index}");
+        for (index, frame) in sheet.frames.iter().enumerate() {
+            let cut = sheet.frame_pixels(index, (width, height), &pixels).ok_or_else(|| {
+                error(GameError::ResourceLoadError(format!("frame {index} is outside {image_file}")))
+            })?;
+            let tag = self.intern(&frame_id(index))?;
+            self.add_image(ctx, tag, frame.frame.w, frame.frame.h, cut).map_err(error)?;
+            let slices = sheet.meta.slices.iter()
+                .filter_map(|slice| {
+                    let bounds = sheet.slice(&slice.name, index)?;
+                    Some((slice.name.clone(), Rect::new(bounds.x as f32, bounds.y as f32, bounds.w as f32, bounds.h as f32)))
+                })
+                .collect();
+            self.slices.insert(AssetTag::new(&frame_id(index)), slices);
+        }
+
+        let playback = self.manifest.playback.clone();
+        let mut anim = |anim_id: &str, frames: Vec<usize>, loop_mode: LoopMode| -> Result<AssetTag, AssetError> {
+            let sprites = frames.iter().map(|index| Sprite::new(AssetTag::new(&frame_id(*index)))).collect();
+            let durations = frames.iter().map(|index| sheet.frames[*index].duration as f32 / 1000.).collect();
+            let tag = self.intern(anim_id)?;
+            self.anims.insert(tag, AnimData::new(sprites, durations, loop_mode, playback.get(anim_id)));
+            Ok(tag)
+        };
+        if sheet.meta.frame_tags.is_empty() {
+            anim(id, (0..sheet.frames.len()).collect(), LoopMode::Loop)?;
+            return Ok(());
+        }
+        let mut states = Vec::new();
+        for frame_tag in sheet.meta.frame_tags.iter() {
+            let frames = sheet.tag_frames(frame_tag);
+            if frames.is_empty() {continue}
+            let loop_mode =
+                if frame_tag.plays_once() {LoopMode::Once}
+                else if frame_tag.ping_pong() {LoopMode::PingPong}
+                else {LoopMode::Loop};
+            states.push((frame_tag.name.clone(), anim(&format!("{id}.{}", frame_tag.name), frames, loop_mode)?));
+        }
+        if !self.manifest.state_machines.contains_key(id) {
+            let tag = self.intern(id)?;
+            self.state_machines.insert(tag, StateMachineData::from_anims(states));
+        }
+        Ok(())
+    }
+    // Decoded here rather than by `Image::new`, so the pixels can go into the atlas too
+    fn decode_image(ctx: &mut Context, file: &str) -> GameResult<(u32, u32, Vec<u8>)> {
+        let mut bytes = Vec::new();
+        filesystem::open(ctx, file)?.read_to_end(&mut bytes)?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|e| GameError::ResourceLoadError(e.to_string()))?
+            .to_rgba8();
+        if image.width() > u16::MAX as u32 || image.height() > u16::MAX as u32 {
+            return Err(GameError::ResourceLoadError("image is too big".to_string()));
+        }
+        Ok((image.width(), image.height(), image.into_raw()))
+    }
+    fn pack_atlas(&mut self, ctx: &mut Context) {
+        self.atlas = match Atlas::build(ctx, &self.pixels) {
+            Ok(atlas) => atlas,
+            Err(e) => {
+                eprintln!("Failed to build the sprite atlas: {e}");
+                None
+            }
+        };
+    }
+    // `None` if the images didn't fit in one texture
+    pub fn atlas(&self) -> Option<&Atlas> {
+        self.atlas.as_ref()
+    }
+    fn load_sound(&mut self, ctx: &mut Context, id: &str, file: &str) -> Result<(), AssetError> {
+        let tag = self.intern(id)?;
+        // Sources need the audio device, which panics when the audio module is off
+        if !self.audio {
+            self.sounds.insert(tag, Sound::Silent);
+            return Ok(());
+        }
+        match audio::Source::new(ctx, file) {
+            Ok(source) => {self.sounds.insert(tag, Sound::Loaded(source));}
+            Err(e) => {
+                Assets::report(id, file, e);
+                self.sounds.entry(tag).or_insert(Sound::Silent);
+            }
+        }
+        Ok(())
+    }
+    fn load_font(&mut self, ctx: &mut Context, id: &str, file: &str) -> Result<(), AssetError> {
+        let tag = self.intern(id)?;
+        match graphics::Font::new(ctx, file) {
+            Ok(font) => {self.fonts.insert(tag, font);}
+            Err(e) => Assets::report(id, file, e),
+        }
+        Ok(())
+    }
+    fn pick_ui_font(&mut self) {
+        if let Some(font) = self.get_font(AssetTag::new(&self.manifest.ui_font)) {
+            self.font = font;
+        }
+    }
+    // Magenta and black checkers, hard to miss
+    fn placeholder(ctx: &mut Context) -> GameResult<Image> {
+        let size = PLACEHOLDER_SIZE as usize;
+        let mut pixels = Vec::with_capacity(size * size * 4);
+        for y in 0..size {
+            for x in 0..size {
+                let magenta = (x / 4 + y / 4) % 2 == 0;
+                pixels.extend_from_slice(if magenta {&[255, 0, 255, 255]} else {&[0, 0, 0, 255]});
+            }
+        }
+        let mut image = Image::from_rgba8(ctx, PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, &pixels)?;
+        image.set_filter(graphics::FilterMode::Nearest);
+        Ok(image)
+    }
+    // Fails if another id already has the same handle
+    fn intern(&mut self, id: &str) -> Result<AssetTag, AssetError> {
+        let tag = AssetTag::new(id);
+        match self.names.get(&tag) {
+            Some(other) if other != id => Err(AssetError::Invalid {
+                id: id.to_string(),
+                cause: format!("has the same handle as {other}"),
+            }),
+            Some(_) => Ok(tag),
+            None => {
+                self.names.insert(tag, id.to_string());
+                Ok(tag)
+            }
+        }
+    }
+    pub fn theme(&self) -> &str {
+        &self.theme
+    }
+    pub fn audio(&self) -> bool {
+        self.audio
+    }
+    // The manifest id behind a handle
+    pub fn name(&self, tag: AssetTag) -> Option<&str> {
+        self.names.get(&tag).map(|name| name.as_str())
+    }
+    // The placeholder if the image is missing
+    pub fn get_image(&self, tag: AssetTag) -> &Image {
+        self.images.get(&tag).unwrap_or(&self.placeholder)
+    }
+    // Size of an image, or of an animation's first frame
+    pub fn image_size(&self, tag: AssetTag) -> Vec2 {
+        let tag = self.get_anim_frame(tag, 0).map(|frame| frame.tag()).unwrap_or(tag);
+        let image = self.get_image(tag);
+        v2!(image.width() as f32, image.height() as f32)
+    }
+    pub fn get_font(&self, tag: AssetTag) -> Option<graphics::Font> {
+        self.fonts.get(&tag).copied()
+    }
+    // Silent if the sound is missing
+    pub fn get_audio(&self, tag: AssetTag) -> &Sound {
+        self.sounds.get(&tag).unwrap_or(&self.silent)
+    }
+    pub fn get_audio_mut(&mut self, tag: AssetTag) -> &mut Sound {
+        match self.sounds.get_mut(&tag) {
+            Some(sound) => sound,
+            None => &mut self.silent,
+        }
+    }
+    pub fn get_anim(&self, tag: AssetTag) -> Option<&AnimData> {
+        self.anims.get(&tag)
+    }
+    pub fn get_anim_frame(&self, tag: AssetTag, frame: usize) -> Option<Sprite> {
+        self.get_anim(tag)?.frames.get(frame).copied()
+    }
+    // Seconds the frame shows for
+    pub fn get_anim_frame_time(&self, tag: AssetTag, frame: usize) -> Option<f32> {
+        self.get_anim(tag)?.durations.get(frame).copied()
+    }
+    // Seconds one pass through all the frames takes
+    pub fn get_anim_duration(&self, tag: AssetTag) -> Option<f32> {
+        Some(self.get_anim(tag)?.durations.iter().sum())
+    }
+    // The event fired when playback reaches the frame, if any
+    pub fn get_anim_event(&self, tag: AssetTag, frame: usize) -> Option<AnimEvent> {
+        self.get_anim(tag)?.events.iter().find(|(event_frame, _)| *event_frame == frame).map(|(_, event)| *event)
+    }
+    pub fn get_anim_length(&self, tag: AssetTag) -> Option<usize> {
+        Some(self.get_anim(tag)?.frames.len())
+    }
+    // A slice of an image, or of an animation's first frame, in pixels from its top left corner
+    pub fn get_slice(&self, tag: AssetTag, name: &str) -> Option<Rect> {
+        let tag = self.get_anim_frame(tag, 0).map(|frame| frame.tag()).unwrap_or(tag);
+        self.slices.get(&tag)?.get(name).copied()
+    }
+    // Where the middle of a slice is, relative to the middle of the image, with y pointing up like the game's
+    pub fn slice_offset(&self, tag: AssetTag, name: &str) -> Option<Vec2> {
+        let slice = self.get_slice(tag, name)?;
+        let size = self.image_size(tag);
+        Some(v2!(slice.x + slice.w / 2. - size.x / 2., size.y / 2. - slice.y - slice.h / 2.))
+    }
+    pub fn get_state_machine(&self, tag: AssetTag) -> Option<&StateMachineData> {
+        self.state_machines.get(&tag)
+    }
 }
 
-impl AssetTag{
-    pub fn cactus_tags() -> Vec<AssetTag> {
-        vec![
-            AssetTag::CactusSmall1,
-            AssetTag::CactusSmall2,
-            AssetTag::CactusSmall3,
-            AssetTag::CactusBig1,
-            AssetTag::CactusBig2,
-            AssetTag::CactusBig3,
-        ]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn handles_come_from_ids(){
+        assert_eq!(AssetTag::new("cloud"), AssetTag::new(&String::from("cloud")));
+        assert_ne!(AssetTag::new("cloud"), AssetTag::new("cloud_small"));
+    }
+    #[test]
+    fn shipped_manifest_has_everything_the_code_uses(){
+        let manifest = AssetManifest::from_toml(include_str!("../resources/assets.toml")).unwrap();
+        let tags = |ids: Vec<&String>| -> Vec<AssetTag> {ids.into_iter().map(|id| AssetTag::new(id)).collect()};
+        let images = tags(manifest.images.keys().collect());
+        let sounds = tags(manifest.sounds.keys().collect());
+        let anims = tags(manifest.animations.keys().collect());
+        for tag in AssetTag::cactus_tags().into_iter().chain(PickupKind::ALL.iter().map(|kind| kind.asset_tag())) {
+            assert!(images.contains(&tag));
+        }
+        for tag in [AssetTag::JUMP_SOUND, AssetTag::DEATH_SOUND, AssetTag::POINT_SOUND, AssetTag::HURT_SOUND] {
+            assert!(sounds.contains(&tag));
+        }
+        for anim in manifest.animations.values() {
+            assert!(anim.frames.iter().all(|frame| manifest.images.contains_key(frame)));
+        }
+
+        assert!(manifest.sheets.contains_key("dino"));
+        assert!(manifest.animations["rock_anim"].playback.scale_with_scroll);
+        assert_eq!(manifest.playback["dino.run"].events[1], FrameEventDef { frame: 1, name: "footstep".to_string() });
+        let dino = SpriteSheet::from_json(include_str!("../resources/images/dino.json")).unwrap();
+        let dino_anims: Vec<String> = dino.meta.frame_tags.iter().map(|tag| format!("dino.{}", tag.name)).collect();
+        for anim in manifest.state_machines["dino_state_machine"].states.values() {
+            assert!(dino_anims.contains(anim));
+        }
+        assert!(dino.slice("feet", 0).is_some());
+        assert!(manifest.fonts.contains_key(&manifest.ui_font));
+        assert!(manifest.check().is_ok());
+    }
+    #[test]
+    fn required_ids_match_the_handles(){
+        assert_eq!(REQUIRED_ANIMS.map(AssetTag::new), [AssetTag::PTERO_ANIM, AssetTag::ROCK_ANIM, AssetTag::TUMBLEWEED_ANIM]);
+        assert_eq!(AssetTag::new(DINO_STATE_MACHINE), AssetTag::DINO_STATE_MACHINE);
+    }
+    #[test]
+    fn themes_fall_back_to_the_default(){
+        let default = AssetManifest::from_toml(r#"
+            [images]
+            cactus = "/images/cactus.png"
+            cloud  = "/images/cloud.png"
+            ground = "/images/ground.png"
+            [sounds]
+            jump = "/sounds/jump.wav"
+        "#).unwrap();
+        let theme = AssetManifest::from_toml(r#"
+            [images]
+            cloud = "/images/snow_cloud.png"
+        "#).unwrap();
+        let themed = default.with_theme(&theme, "/themes/winter", |file| file == "/themes/winter/images/cactus.png");
+        assert_eq!(themed.images["cactus"], "/themes/winter/images/cactus.png");
+        assert_eq!(themed.images["cloud"], "/themes/winter/images/snow_cloud.png");
+        assert_eq!(themed.images["ground"], "/images/ground.png");
+        assert_eq!(themed.sounds["jump"], "/sounds/jump.wav");
+
+        let shipped = AssetManifest::from_toml(include_str!("../resources/assets.toml")).unwrap();
+        let winter = AssetManifest::from_toml(include_str!("../resources/themes/winter/assets.toml")).unwrap();
+        assert!(winter.images.keys().all(|id| shipped.images.contains_key(id)));
     }
-}
\ No newline at end of file
+    #[test]
+    fn broken_references_are_reported(){
+        let manifest = AssetManifest::from_toml(r#"
+            [animations]
+            run = { frames = ["dino_run_l"], fps = 8 }
+
+            [state_machines.dino]
+            start  = "run"
+            states = { run = "run", jump = "jumping" }
+        "#).unwrap();
+        let error = manifest.check().unwrap_err();
+        assert_eq!(error.to_string(), "dino refers to asset jumping, which isn't in the manifest");
+
+        let manifest = AssetManifest::from_toml(r#"
+            [animations]
+            run = { frames = ["dino_run_l"], fps = 8 }
+
+            [state_machines.dino]
+            start       = "run"
+            states      = { run = "run" }
+            transitions = [{ to = "run", when = ["on ground"] }]
+        "#).unwrap();
+        let error = manifest.check().unwrap_err();
+        assert_eq!(error.to_string(), "Asset dino is invalid: can't read condition \"on ground\"");
+    }
+}
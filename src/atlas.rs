@@ -0,0 +1,158 @@
+// Every image packed into one texture, so entity sprites can be drawn together in a `SpriteBatch`
+// instead of one draw call each. The atlas is built when the assets load.
+use crate::prelude::*;
+use ggez::graphics::{DrawParam, Rect, spritebatch::SpriteBatch};
+use std::collections::HashMap;
+
+// Largest atlas side. If the images don't fit, they're drawn one by one.
+const MAX_ATLAS_SIZE: u32 = 4096;
+// Empty pixels around each image, so neighbours never bleed in
+const PADDING: u32 = 1;
+
+type Pixels = (u32, u32);   // (x, y) or (width, height)
+
+// Shelf packing: the images go left to right in rows, tallest first.
+// Returns the top left corner of each image and the size of the atlas.
+pub fn pack(sizes: &[Pixels]) -> Option<(Vec<Pixels>, Pixels)> {
+    let padded = |size: u32| size + PADDING * 2;
+    let widest = sizes.iter().map(|s| padded(s.0)).max().unwrap_or(1);
+    let area: u32 = sizes.iter().map(|s| padded(s.0) * padded(s.1)).sum();
+    let width = widest.max((area as f32).sqrt().ceil() as u32).next_power_of_two();
+    if width > MAX_ATLAS_SIZE {return None}
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1).then(a.cmp(b)));
+    let mut corners = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf) = (0, 0, 0);
+    for i in order {
+        let (w, h) = (padded(sizes[i].0), padded(sizes[i].1));
+        if x + w > width {
+            x = 0;
+            y += shelf;
+            shelf = 0;
+        }
+        corners[i] = (x + PADDING, y + PADDING);
+        x += w;
+        shelf = shelf.max(h);
+    }
+    let height = y + shelf;
+    if height > MAX_ATLAS_SIZE {return None}
+    Some((corners, (width, height.max(1))))
+}
+
+// Copies RGBA pixels into a bigger RGBA image at (x, y)
+fn blit(dest: &mut [u8], dest_width: u32, src: &[u8], (x, y): Pixels, (w, h): Pixels) {
+    for row in 0..h {
+        let from = (row * w * 4) as usize;
+        let to = (((y + row) * dest_width + x) * 4) as usize;
+        dest[to..to + (w * 4) as usize].copy_from_slice(&src[from..from + (w * 4) as usize]);
+    }
+}
+
+pub struct Atlas {
+    image: Image,
+    // The part of the atlas each image is in, in UV coordinates
+    rects: HashMap<AssetTag, Rect>,
+}
+
+impl Atlas {
+    // Takes each image's size and RGBA pixels. Returns `None` if they don't fit.
+    pub fn build(ctx: &mut Context, images: &HashMap<AssetTag, (u32, u32, Vec<u8>)>) -> GameResult<Option<Atlas>> {
+        let mut tags: Vec<AssetTag> = images.keys().copied().collect();
+        tags.sort();
+        let sizes: Vec<Pixels> = tags.iter().map(|tag| (images[tag].0, images[tag].1)).collect();
+        let (corners, (width, height)) = match pack(&sizes) {
+            Some(packed) => packed,
+            None => return Ok(None),
+        };
+        let mut pixels = vec![0; (width * height * 4) as usize];
+        let mut rects = HashMap::new();
+        for (i, tag) in tags.iter().enumerate() {
+            let (w, h, src) = &images[tag];
+            blit(&mut pixels, width, src, corners[i], (*w, *h));
+            rects.insert(*tag, Rect::new(
+                corners[i].0 as f32 / width as f32,
+                corners[i].1 as f32 / height as f32,
+                *w as f32 / width as f32,
+                *h as f32 / height as f32,
+            ));
+        }
+        let mut image = Image::from_rgba8(ctx, width as u16, height as u16, &pixels)?;
+        image.set_filter(graphics::FilterMode::Nearest);
+        Ok(Some(Atlas { image, rects }))
+    }
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+    pub fn uv(&self, tag: AssetTag) -> Option<Rect> {
+        self.rects.get(&tag).copied()
+    }
+}
+
+// Collects sprites that are in the atlas and draws them all at once. Drawing anything
+// that isn't in the atlas flushes what's collected first, so the draw order stays the same.
+pub struct SpriteBatcher {
+    batch: Option<SpriteBatch>,
+    draw_calls: usize,
+}
+
+impl SpriteBatcher {
+    pub fn new(assets: &Assets) -> SpriteBatcher {
+        SpriteBatcher {
+            batch: assets.atlas().map(|atlas| SpriteBatch::new(atlas.image().clone())),
+            draw_calls: 0,
+        }
+    }
+    pub fn draw(&mut self, ctx: &mut Context, assets: &Assets, tag: AssetTag, param: DrawParam) -> GameResult {
+        let uv = assets.atlas().and_then(|atlas| atlas.uv(tag));
+        if let (Some(batch), Some(uv)) = (self.batch.as_mut(), uv) {
+            batch.add(param.src(uv));
+            return Ok(());
+        }
+        self.flush(ctx)?;
+        self.draw_calls += 1;
+        graphics::draw(ctx, assets.get_image(tag), param)
+    }
+    pub fn flush(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some(batch) = self.batch.as_mut() {
+            if batch.get_sprites().is_empty() {return Ok(())}
+            self.draw_calls += 1;
+            graphics::draw(ctx, batch, DrawParam::new())?;
+            batch.clear();
+        }
+        Ok(())
+    }
+    pub fn draw_calls(&self) -> usize {
+        self.draw_calls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn packed_images_dont_overlap(){
+        let sizes = [(88, 94), (34, 70), (68, 70), (1200, 28), (46, 40), (92, 80), (16, 16)];
+        let (corners, (width, height)) = pack(&sizes).unwrap();
+        assert!(width.is_power_of_two());
+        let rects: Vec<(u32, u32, u32, u32)> = corners.iter().zip(sizes.iter())
+            .map(|(c, s)| (c.0, c.1, c.0 + s.0, c.1 + s.1))
+            .collect();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.2 + PADDING <= width && a.3 + PADDING <= height);
+            for b in rects.iter().skip(i + 1) {
+                assert!(a.2 + PADDING <= b.0 || b.2 + PADDING <= a.0 || a.3 + PADDING <= b.1 || b.3 + PADDING <= a.1);
+            }
+        }
+        assert!(pack(&[(MAX_ATLAS_SIZE, 8)]).is_none());
+    }
+    #[test]
+    fn blit_copies_rows(){
+        let mut dest = vec![0; 4 * 3 * 4];
+        let src: Vec<u8> = (1..=16).collect();
+        blit(&mut dest, 4, &src, (1, 1), (2, 2));
+        assert_eq!(&dest[20..28], &src[0..8]);
+        assert_eq!(&dest[36..44], &src[8..16]);
+        assert_eq!(dest.iter().filter(|byte| **byte != 0).count(), 16);
+    }
+}
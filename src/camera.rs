@@ -0,0 +1,97 @@
+// View onto the world. Everything drawn between `apply` and `reset` goes through it.
+use crate::prelude::*;
+
+// Screen pixels the view moves at full trauma
+const MAX_SHAKE: f32 = 16.;
+// Trauma lost per second
+const TRAUMA_DECAY: f32 = 1.2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    // World point shown at the center of the screen
+    pub position: Vec2,
+    pub zoom: f32,
+    // From 0 to 1. The shake grows with its square and wears off over time.
+    trauma: f32,
+    shake: Vec2,
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            position: v2!(),
+            zoom: 1.,
+            trauma: 0.,
+            shake: v2!(),
+        }
+    }
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0., 1.);
+    }
+    pub fn shaking(&self) -> bool {
+        self.trauma > 0.
+    }
+    pub fn update(&mut self, rng: &mut Rand32, dt: f32) {
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.);
+        let strength = MAX_SHAKE * self.trauma * self.trauma;
+        self.shake = v2!(rng.rand_float() * 2. - 1., rng.rand_float() * 2. - 1.) * strength;
+    }
+    // Starts zoomed in by `zoom_out` and eases back to the normal view as `progress` goes from 0 to 1,
+    // so the view never shows more than the screen the game was laid out for
+    pub fn zoom_out(&mut self, zoom_out: f32, progress: f32) {
+        self.zoom = 1. + zoom_out * (1. - progress.clamp(0., 1.));
+    }
+
+    // The part of the screen plane, as laid out by `world_to_screen_coords`, that ends up on screen
+    fn view(&self, screen_size: Screen2) -> graphics::Rect {
+        let size = v2!(screen_size.0, screen_size.1) / self.zoom;
+        let center = v2!(screen_size.0 / 2. + self.position.x, screen_size.1 / 2. - self.position.y) + self.shake;
+        graphics::Rect::new(center.x - size.x / 2., center.y - size.y / 2., size.x, size.y)
+    }
+    pub fn world_to_screen(&self, screen_size: Screen2, point: Vec2) -> Vec2 {
+        let view = self.view(screen_size);
+        (world_to_screen_coords(screen_size, point) - v2!(view.x, view.y)) * self.zoom
+    }
+    pub fn screen_to_world(&self, screen_size: Screen2, point: Vec2) -> Vec2 {
+        let view = self.view(screen_size);
+        screen_to_world_coords(screen_size, point / self.zoom + v2!(view.x, view.y))
+    }
+
+    pub fn apply(&self, ctx: &mut Context, screen_size: Screen2) -> GameResult {
+        graphics::set_screen_coordinates(ctx, self.view(screen_size))
+    }
+    pub fn reset(ctx: &mut Context, screen_size: Screen2) -> GameResult {
+        graphics::set_screen_coordinates(ctx, graphics::Rect::new(0., 0., screen_size.0, screen_size.1))
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn screen_and_world_round_trip(){
+        let mut camera = Camera::new();
+        camera.position = v2!(40., -20.);
+        camera.zoom_out(0.25, 0.);
+        assert_eq!(camera.world_to_screen(SCREEN, camera.position), v2!(SCREEN.0 / 2., SCREEN.1 / 2.));
+        let point = v2!(-300., 75.);
+        let back = camera.screen_to_world(SCREEN, camera.world_to_screen(SCREEN, point));
+        assert!((back - point).length() < 0.001);
+    }
+    #[test]
+    fn trauma_wears_off(){
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        let mut camera = Camera::new();
+        camera.add_trauma(2.);
+        camera.update(&mut rng, 0.1);
+        assert!(camera.shaking());
+        camera.update(&mut rng, 1.);
+        assert!(!camera.shaking());
+    }
+}
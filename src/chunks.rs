@@ -0,0 +1,146 @@
+// Authored obstacle patterns, loaded from `resources/chunks.toml`
+use crate::prelude::*;
+use serde::Deserialize;
+
+pub const CHUNKS_PATH: &str = "/chunks.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum ChunkObstacleKind {
+    #[serde(rename = "cactus_small_1")] CactusSmall1,
+    #[serde(rename = "cactus_small_2")] CactusSmall2,
+    #[serde(rename = "cactus_small_3")] CactusSmall3,
+    #[serde(rename = "cactus_big_1")]   CactusBig1,
+    #[serde(rename = "cactus_big_2")]   CactusBig2,
+    #[serde(rename = "cactus_big_3")]   CactusBig3,
+    #[serde(rename = "ptero")]          Ptero,
+    #[serde(rename = "rock")]           Rock,
+    #[serde(rename = "tumbleweed")]     Tumbleweed,
+}
+
+impl ChunkObstacleKind {
+    pub fn asset_tag(&self) -> AssetTag {
+        match self {
+            ChunkObstacleKind::CactusSmall1 => AssetTag::CACTUS_SMALL_1,
+            ChunkObstacleKind::CactusSmall2 => AssetTag::CACTUS_SMALL_2,
+            ChunkObstacleKind::CactusSmall3 => AssetTag::CACTUS_SMALL_3,
+            ChunkObstacleKind::CactusBig1   => AssetTag::CACTUS_BIG_1,
+            ChunkObstacleKind::CactusBig2   => AssetTag::CACTUS_BIG_2,
+            ChunkObstacleKind::CactusBig3   => AssetTag::CACTUS_BIG_3,
+            ChunkObstacleKind::Ptero        => AssetTag::PTERO_ANIM,
+            ChunkObstacleKind::Rock         => AssetTag::ROCK_ANIM,
+            ChunkObstacleKind::Tumbleweed   => AssetTag::TUMBLEWEED_ANIM,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct ChunkObstacle {
+    pub kind: ChunkObstacleKind,
+    // Gap from the previous obstacle's right edge (or the screen edge for the first one)
+    #[serde(default)]
+    pub spacing: f32,
+    // Height above the ground and flight pattern, only used by pteros
+    pub height: Option<f32>,
+    #[serde(default)]
+    pub pattern: FlightPattern,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Chunk {
+    pub name: String,
+    pub tier: u32,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    pub obstacles: Vec<ChunkObstacle>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct ChunkLibrary {
+    #[serde(default, rename = "chunk")]
+    pub chunks: Vec<Chunk>,
+}
+
+impl ChunkLibrary {
+    pub fn from_toml(src: &str) -> Result<ChunkLibrary, toml::de::Error> {
+        let mut library: ChunkLibrary = toml::from_str(src)?;
+        library.chunks.retain(|chunk| !chunk.obstacles.is_empty() && chunk.weight > 0.);
+        Ok(library)
+    }
+
+    // Falls back to an empty library, which means purely random spawns
+    pub fn load(ctx: &mut Context) -> ChunkLibrary {
+        load_toml_or_default(ctx, CHUNKS_PATH, ChunkLibrary::from_toml)
+    }
+
+    // Weight of a chunk at the given tier. Chunks above the tier are excluded,
+    // and each tier below it halves the weight.
+    fn weight(chunk: &Chunk, tier: u32) -> f32 {
+        if chunk.tier > tier {return 0.}
+        chunk.weight / 2f32.powi((tier - chunk.tier) as i32)
+    }
+
+    pub fn pick(&self, tier: u32, rng: &mut Rand32) -> Option<&Chunk> {
+        let total: f32 = self.chunks.iter().map(|c| ChunkLibrary::weight(c, tier)).sum();
+        if total <= 0. {return None}
+        let mut roll = rng.rand_float() * total;
+        let mut last = None;
+        for chunk in self.chunks.iter() {
+            let weight = ChunkLibrary::weight(chunk, tier);
+            if weight <= 0. {continue}
+            if roll < weight {return Some(chunk)}
+            roll -= weight;
+            last = Some(chunk);
+        }
+        last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const SRC: &str = r#"
+        [[chunk]]
+        name = "easy"
+        tier = 1
+        obstacles = [{ kind = "cactus_small_1" }]
+
+        [[chunk]]
+        name = "double cactus then low ptero"
+        tier = 2
+        weight = 3.0
+        obstacles = [
+            { kind = "cactus_small_2" },
+            { kind = "cactus_big_1", spacing = 12.0 },
+            { kind = "ptero", spacing = 350.0, height = 35.0, pattern = { type = "sine_bob", amplitude = 10.0, wavelength = 300.0 } },
+        ]
+    "#;
+
+    #[test]
+    fn parse_chunks(){
+        let library = ChunkLibrary::from_toml(SRC).unwrap();
+        assert_eq!(library.chunks.len(), 2);
+        assert_eq!(library.chunks[0].weight, 1.0);
+        assert_eq!(library.chunks[1].obstacles[2].kind, ChunkObstacleKind::Ptero);
+        assert_eq!(library.chunks[1].obstacles[2].height, Some(35.0));
+        assert_eq!(library.chunks[0].obstacles[0].pattern, FlightPattern::Straight);
+        assert_eq!(library.chunks[1].obstacles[2].pattern, FlightPattern::SineBob { amplitude: 10.0, wavelength: 300.0 });
+    }
+
+    #[test]
+    fn pick_respects_tier(){
+        let library = ChunkLibrary::from_toml(SRC).unwrap();
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        assert!(library.pick(0, &mut rng).is_none());
+        for _ in 0..20 {
+            assert_eq!(library.pick(1, &mut rng).unwrap().name, "easy");
+        }
+        let picked_hard = (0..100)
+            .filter(|_| library.pick(2, &mut rng).unwrap().tier == 2)
+            .count();
+        assert!(picked_hard > 70);
+    }
+}
@@ -0,0 +1,408 @@
+use crate::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// Parameters past this many can't be set, and state machines that read more fail to load
+const MAX_ANIM_PARAMS: usize = 8;
+// Transitions from this state apply in every state
+const ANY_STATE: &str = "*";
+
+// Name of a value state machine conditions look at, hashed like asset ids
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AnimParam(AssetTag);
+
+impl AnimParam {
+    pub const fn new(name: &str) -> AnimParam {
+        AnimParam(AssetTag::new(name))
+    }
+
+    pub const ON_GROUND: AnimParam = AnimParam::new("on_ground");
+    pub const DEAD:      AnimParam = AnimParam::new("dead");
+}
+
+// Values gameplay code sets on an entity for its `AnimStateMachine` to react to.
+// Ones that were never set read as 0, and true is 1.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AnimParams {
+    values: [(Option<AnimParam>, f32); MAX_ANIM_PARAMS],
+}
+
+impl AnimParams {
+    pub fn get(&self, param: AnimParam) -> f32 {
+        self.values.iter().find(|(name, _)| *name == Some(param)).map_or(0., |(_, value)| *value)
+    }
+    // Once every slot is taken, writes to new parameters are ignored. State machines can't
+    // read more than `MAX_ANIM_PARAMS`, so those are ones nothing reads.
+    pub fn set(&mut self, param: AnimParam, value: f32) {
+        let slot = self.values.iter().position(|(name, _)| *name == Some(param))
+            .or_else(|| self.values.iter().position(|(name, _)| name.is_none()));
+        if let Some(slot) = slot {
+            self.values[slot] = (Some(param), value);
+        }
+    }
+    pub fn set_bool(&mut self, param: AnimParam, value: bool) {
+        self.set(param, value as u8 as f32);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Compare {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+// "on_ground", "!dead", or a comparison with a number like "speed >= 1.5"
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Condition {
+    param: AnimParam,
+    compare: Compare,
+    value: f32,
+}
+
+impl Condition {
+    pub fn parse(src: &str) -> Option<Condition> {
+        let is_name = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        let condition = |name: &str, compare, value| {
+            if is_name(name) {Some(Condition { param: AnimParam::new(name), compare, value })} else {None}
+        };
+        let src = src.trim();
+        // Longer operators first, so "<=" isn't read as "<"
+        let operators = [
+            ("<=", Compare::LessOrEqual),
+            (">=", Compare::GreaterOrEqual),
+            ("==", Compare::Equal),
+            ("!=", Compare::NotEqual),
+            ("<",  Compare::Less),
+            (">",  Compare::Greater),
+        ];
+        for (operator, compare) in operators {
+            if let Some((name, value)) = src.split_once(operator) {
+                return condition(name.trim(), compare, value.trim().parse().ok()?);
+            }
+        }
+        match src.strip_prefix('!') {
+            Some(name) => condition(name.trim(), Compare::Equal, 0.),
+            None       => condition(src, Compare::NotEqual, 0.),
+        }
+    }
+    pub fn holds(&self, params: &AnimParams) -> bool {
+        let value = params.get(self.param);
+        match self.compare {
+            Compare::Less           => value < self.value,
+            Compare::LessOrEqual    => value <= self.value,
+            Compare::Greater        => value > self.value,
+            Compare::GreaterOrEqual => value >= self.value,
+            Compare::Equal          => value == self.value,
+            Compare::NotEqual       => value != self.value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TransitionDef {
+    // State name, or "*" for any state
+    #[serde(default = "any_state")]
+    pub from: String,
+    pub to: String,
+    // Conditions that all have to hold
+    #[serde(default)]
+    pub when: Vec<String>,
+    // How much of the current clip has to have played first, 1 being all of it
+    #[serde(default)]
+    pub exit_time: Option<f32>,
+    // Seconds the old clip fades out over
+    #[serde(default)]
+    pub crossfade: f32,
+}
+
+fn any_state() -> String {
+    ANY_STATE.to_string()
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct StateMachineDef {
+    pub start: String,
+    // Animation id for each state name
+    pub states: BTreeMap<String, String>,
+    // Checked in order, the first one that can be taken is
+    pub transitions: Vec<TransitionDef>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimTransition {
+    // `None` for any state
+    from: Option<usize>,
+    to: usize,
+    conditions: Vec<Condition>,
+    exit_time: Option<f32>,
+    crossfade: f32,
+}
+
+// A state machine from the asset manifest, with the names resolved
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateMachineData {
+    states: Vec<(String, AssetTag)>,
+    start: usize,
+    transitions: Vec<AnimTransition>,
+}
+
+impl StateMachineData {
+    pub fn from_def(id: &str, def: &StateMachineDef) -> Result<StateMachineData, AssetError> {
+        let states: Vec<(String, AssetTag)> = def.states.iter().map(|(state, anim)| (state.clone(), AssetTag::new(anim))).collect();
+        let state = |name: &String| -> Result<usize, AssetError> {
+            states.iter().position(|(state, _)| state == name)
+                .ok_or_else(|| AssetError::Undefined { id: name.clone(), wanted_by: id.to_string() })
+        };
+        let mut transitions = Vec::new();
+        for transition in def.transitions.iter() {
+            let conditions = transition.when.iter()
+                .map(|src| Condition::parse(src).ok_or_else(|| AssetError::Invalid { id: id.to_string(), cause: format!("can't read condition \"{src}\"") }))
+                .collect::<Result<Vec<Condition>, AssetError>>()?;
+            transitions.push(AnimTransition {
+                from: if transition.from == ANY_STATE {None} else {Some(state(&transition.from)?)},
+                to: state(&transition.to)?,
+                conditions,
+                exit_time: transition.exit_time,
+                crossfade: transition.crossfade.max(0.),
+            });
+        }
+        // An entity's `AnimParams` only holds so many values
+        let mut params: Vec<AnimParam> = transitions.iter().flat_map(|t| t.conditions.iter().map(|c| c.param)).collect();
+        params.sort_by_key(|param| param.0);
+        params.dedup();
+        if params.len() > MAX_ANIM_PARAMS {
+            return Err(AssetError::Invalid {
+                id: id.to_string(),
+                cause: format!("uses {} parameters, at most {MAX_ANIM_PARAMS} are supported", params.len()),
+            });
+        }
+        Ok(StateMachineData {
+            start: state(&def.start)?,
+            states,
+            transitions,
+        })
+    }
+    // One state per animation, starting in the first one, with no transitions
+    pub fn from_anims(states: Vec<(String, AssetTag)>) -> StateMachineData {
+        StateMachineData {
+            states,
+            start: 0,
+            transitions: Vec::new(),
+        }
+    }
+    pub fn state_anim(&self, state: usize) -> Option<AssetTag> {
+        self.states.get(state).map(|(_, anim)| *anim)
+    }
+    pub fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|(state, _)| state == name)
+    }
+    // The transition to take from `current`, given how much of its clip has played
+    pub fn next(&self, current: usize, params: &AnimParams, progress: f32) -> Option<&AnimTransition> {
+        self.transitions.iter().find(|transition| {
+            transition.from.map_or(true, |from| from == current)
+                && transition.to != current
+                && transition.exit_time.map_or(true, |exit_time| progress >= exit_time)
+                && transition.conditions.iter().all(|condition| condition.holds(params))
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Crossfade {
+    // The clip being faded out
+    anim: Animation,
+    left: f32,
+    duration: f32,
+}
+
+// Plays the animation of the state the entity is in, switching states when the entity's
+// `AnimParams` meet a transition's conditions. The states and transitions come from the manifest.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnimStateMachine {
+    asset_tag: AssetTag,
+    current_state: usize,
+    fade: Option<Crossfade>,
+}
+
+impl AnimStateMachine {
+    pub fn new(assets: &Assets, asset_tag: AssetTag) -> Result<AnimStateMachine, AssetError> {
+        let start = AnimStateMachine::data(assets, asset_tag)?.start;
+        Ok(AnimStateMachine {
+            asset_tag,
+            current_state: start,
+            fade: None,
+        })
+    }
+    fn data(assets: &Assets, asset_tag: AssetTag) -> Result<&StateMachineData, AssetError> {
+        assets.get_state_machine(asset_tag).ok_or_else(|| AssetError::Undefined {
+            id: AnimStateMachine::name(assets, asset_tag),
+            wanted_by: "A state machine".to_string(),
+        })
+    }
+    fn name(assets: &Assets, asset_tag: AssetTag) -> String {
+        assets.name(asset_tag).map_or_else(|| format!("{asset_tag:?}"), str::to_string)
+    }
+    // The animation to add alongside the state machine
+    pub fn start_anim(&self, assets: &Assets) -> Result<Animation, AssetError> {
+        let data = AnimStateMachine::data(assets, self.asset_tag)?;
+        let anim = data.state_anim(self.current_state).or(data.state_anim(data.start)).ok_or_else(|| AssetError::Invalid {
+            id: AnimStateMachine::name(assets, self.asset_tag),
+            cause: "has no states".to_string(),
+        })?;
+        Animation::new(assets, anim)
+    }
+    // Switches straight to a state, for the machines that have no transitions
+    pub fn set_state(&mut self, ecs: &mut ECS, assets: &Assets, entity_id: usize, name: &str) {
+        if let Some(state) = assets.get_state_machine(self.asset_tag).and_then(|data| data.state_index(name)) {
+            if state != self.current_state {self.enter(ecs, assets, entity_id, state, 0.)}
+        }
+    }
+    // The clip fading out, drawn under the current one
+    pub fn fading(&self) -> Option<Animation> {
+        self.fade.map(|fade| fade.anim)
+    }
+
+    pub fn update_state(&mut self, ecs: &mut ECS, assets: &Assets, entity_id: usize, dt: f32) {
+        let data = match assets.get_state_machine(self.asset_tag) {
+            Some(data) => data,
+            None => return,
+        };
+        // The state machine changed when the assets were reloaded
+        if self.current_state >= data.states.len() {
+            self.enter(ecs, assets, entity_id, data.start, 0.);
+        }
+        self.update_fade(ecs, entity_id, dt);
+
+        let params = ecs.get_component::<AnimParams>(entity_id).unwrap_or_default();
+        let progress = ecs.get_component::<Animation>(entity_id).map_or(1., |anim| anim.progress(assets));
+        if let Some(transition) = data.next(self.current_state, &params, progress) {
+            let (to, crossfade) = (transition.to, transition.crossfade);
+            self.enter(ecs, assets, entity_id, to, crossfade);
+        }
+    }
+    fn enter(&mut self, ecs: &mut ECS, assets: &Assets, entity_id: usize, state: usize, crossfade: f32) {
+        let anim_tag = match assets.get_state_machine(self.asset_tag).and_then(|data| data.state_anim(state)) {
+            Some(anim_tag) => anim_tag,
+            None => return,
+        };
+        let mut anim = match Animation::new(assets, anim_tag) {
+            Ok(anim) => anim,
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        };
+        self.current_state = state;
+        let old = ecs.get_component::<Animation>(entity_id);
+        if let Some(old) = old {anim.follow(&old)}
+        self.fade = match old {
+            Some(old) if crossfade > 0. => {
+                anim.set_alpha(0.);
+                Some(Crossfade { anim: old, left: crossfade, duration: crossfade })
+            }
+            _ => None,
+        };
+        ecs.set_component::<Animation>(entity_id, anim);
+    }
+    fn update_fade(&mut self, ecs: &mut ECS, entity_id: usize, dt: f32) {
+        let mut fade = match self.fade {
+            Some(fade) => fade,
+            None => return,
+        };
+        fade.left -= dt;
+        let t = (fade.left / fade.duration).max(0.);
+        fade.anim.set_alpha(t);
+        if let Some(mut anim) = ecs.get_component::<Animation>(entity_id) {
+            anim.set_alpha(1. - t);
+            fade.anim.set_visible(anim.visible());
+            ecs.set_component(entity_id, anim);
+        }
+        self.fade = if fade.left > 0. {Some(fade)} else {None};
+    }
+}
+
+impl Update for AnimStateMachine{
+    fn update(ecs: &mut ECS, assets: &Assets, _rng: &mut Rand32, entity_id: usize, _time: f32, dt: f32) {
+        let mut machine = ecs.get_component::<AnimStateMachine>(entity_id).unwrap();
+        machine.update_state(ecs, assets, entity_id, dt);
+        ecs.set_component(entity_id, machine);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn conditions_read_params(){
+        let mut params = AnimParams::default();
+        params.set_bool(AnimParam::ON_GROUND, true);
+        params.set(AnimParam::new("speed"), 2.);
+        let holds = |src: &str| Condition::parse(src).unwrap().holds(&params);
+        assert!(holds("on_ground"));
+        assert!(!holds("!on_ground"));
+        assert!(holds("!dead"));
+        assert!(holds("speed >= 2"));
+        assert!(!holds("speed<2"));
+        assert!(holds("speed != 0.5"));
+        assert!(Condition::parse("speed >").is_none());
+        assert!(Condition::parse("on ground").is_none());
+    }
+    #[test]
+    fn params_past_the_cap_are_ignored(){
+        let mut params = AnimParams::default();
+        for i in 0..MAX_ANIM_PARAMS {
+            params.set(AnimParam::new(&format!("param_{i}")), 1.);
+        }
+        params.set(AnimParam::new("one_too_many"), 1.);
+        assert_eq!(params.get(AnimParam::new("one_too_many")), 0.);
+        params.set(AnimParam::new("param_0"), 2.);
+        assert_eq!(params.get(AnimParam::new("param_0")), 2.);
+    }
+    #[test]
+    fn transitions_follow_conditions_and_exit_time(){
+        let def: StateMachineDef = toml::from_str(r#"
+            start  = "run"
+            states = { run = "dino.run", jump = "dino.jump", dead = "dino.dead" }
+            transitions = [
+                { from = "run",  to = "jump", when = ["!on_ground"] },
+                { from = "jump", to = "run",  when = ["on_ground"], exit_time = 0.5 },
+                { to = "dead", when = ["dead"] },
+            ]
+        "#).unwrap();
+        let data = StateMachineData::from_def("dino", &def).unwrap();
+        let (run, jump, dead) = (data.state_index("run").unwrap(), data.state_index("jump").unwrap(), data.state_index("dead").unwrap());
+        assert_eq!(data.start, run);
+        assert_eq!(data.state_anim(jump), Some(AssetTag::new("dino.jump")));
+
+        let mut params = AnimParams::default();
+        params.set_bool(AnimParam::ON_GROUND, true);
+        assert!(data.next(run, &params, 0.).is_none());
+        params.set_bool(AnimParam::ON_GROUND, false);
+        assert_eq!(data.next(run, &params, 0.).unwrap().to, jump);
+        params.set_bool(AnimParam::ON_GROUND, true);
+        assert!(data.next(jump, &params, 0.2).is_none());
+        assert_eq!(data.next(jump, &params, 0.5).unwrap().to, run);
+        params.set_bool(AnimParam::DEAD, true);
+        assert_eq!(data.next(jump, &params, 0.).unwrap().to, dead);
+        assert!(data.next(dead, &params, 0.).is_none());
+
+        let too_many_params = StateMachineDef {
+            transitions: (0..=MAX_ANIM_PARAMS).map(|i| TransitionDef {
+                from: "run".to_string(),
+                to: "jump".to_string(),
+                when: vec![format!("param_{i}")],
+                exit_time: None,
+                crossfade: 0.,
+            }).collect(),
+            ..def.clone()
+        };
+        assert!(StateMachineData::from_def("dino", &too_many_params).is_err());
+        let broken = StateMachineDef { start: "fly".to_string(), ..def };
+        assert!(StateMachineData::from_def("dino", &broken).is_err());
+    }
+}
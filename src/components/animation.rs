@@ -1,81 +1,237 @@
 use crate::prelude::*;
+use serde::Deserialize;
+
+// Frames shorter than this are stretched, so a zero duration can't stall the game
+const MIN_FRAME_TIME: f32 = 0.001;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopMode {
+    #[default]
+    Loop,
+    // Plays once and holds the last frame
+    Once,
+    // Back and forth, without showing the ends twice
+    PingPong,
+}
+
+// Something that happens on a frame of an animation, like a footstep.
+// Named in the asset manifest and hashed like asset ids.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AnimEvent(AssetTag);
+
+impl AnimEvent {
+    pub const fn new(name: &str) -> AnimEvent {
+        AnimEvent(AssetTag::new(name))
+    }
+
+    pub const FOOTSTEP: AnimEvent = AnimEvent::new("footstep");
+}
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Animation{
     asset_tag: AssetTag,
-    fps: u8,
     len: usize,
+    loop_mode: LoopMode,
     current_frame: usize,
-    frame_time: f32,
-    next_frame_upd: f32,
+    // Seconds spent on the current frame
+    frame_elapsed: f32,
+    // Ping-pong direction
+    backwards: bool,
+    finished: bool,
+    // Playback speed, and whether it follows the scroll speed
+    speed: f32,
+    scale_with_scroll: bool,
+    // Fired during the last update
+    event: Option<AnimEvent>,
+    // Seconds played since the start, sped up or slowed down along with it
+    played: f32,
+    transform: SpriteTransform,
+    visible: bool,
 }
 
 impl Animation {
-    pub fn new(assets: &Assets, asset_tag: AssetTag) -> Animation {
-        let fps = assets.get_anim_fps(asset_tag).unwrap();
-        Animation{
+    pub fn new(assets: &Assets, asset_tag: AssetTag) -> Result<Animation, AssetError> {
+        if assets.get_anim(asset_tag).is_none() {
+            return Err(AssetError::Undefined {
+                id: assets.name(asset_tag).map_or_else(|| format!("{asset_tag:?}"), str::to_string),
+                wanted_by: "An animation".to_string(),
+            });
+        }
+        let mut anim = Animation{
             asset_tag,
-            len: assets.get_anim_length(asset_tag).unwrap(),
-            fps,
+            len: 1,
+            loop_mode: LoopMode::Loop,
             current_frame: 0,
-            frame_time: 1.0 / fps as f32,
-            next_frame_upd: 0.0,
+            frame_elapsed: 0.0,
+            backwards: false,
+            finished: false,
+            speed: 1.0,
+            scale_with_scroll: false,
+            event: None,
+            played: 0.0,
+            transform: SpriteTransform::default(),
+            visible: true,
+        };
+        anim.refresh(assets);
+        Ok(anim)
+    }
+    pub fn set_visible(&mut self, visible: bool){
+        self.visible = visible;
+    }
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+    // Applies to every frame
+    pub fn transform(&self) -> SpriteTransform {
+        self.transform
+    }
+    pub fn transform_mut(&mut self) -> &mut SpriteTransform {
+        &mut self.transform
+    }
+    pub fn set_alpha(&mut self, alpha: f32){
+        self.transform.alpha = alpha;
+    }
+    // Keeps the playback speed of the animation this one replaces
+    pub fn follow(&mut self, old: &Animation){
+        if self.scale_with_scroll {self.speed = old.speed}
+        self.transform = SpriteTransform { alpha: self.transform.alpha, ..old.transform };
+    }
+    // Picks up changes to the animation's definition when the assets are reloaded
+    pub fn refresh(&mut self, assets: &Assets){
+        if let Some(anim) = assets.get_anim(self.asset_tag) {
+            self.len = anim.frames.len().max(1);
+            self.loop_mode = anim.loop_mode;
+            self.scale_with_scroll = anim.scale_with_scroll;
+            self.current_frame = self.current_frame.min(self.len - 1);
+        }
+    }
+    // How much faster than normal the game scrolls. Only affects animations set to follow it.
+    pub fn set_scroll_factor(&mut self, factor: f32){
+        self.speed = if self.scale_with_scroll {factor.max(0.)} else {1.0};
+    }
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+    // How many times the animation played through, as a fraction. 1 once it's finished.
+    pub fn progress(&self, assets: &Assets) -> f32 {
+        if self.finished {return 1.}
+        let duration = assets.get_anim_duration(self.asset_tag).unwrap_or(0.);
+        if duration <= 0. {1.} else {self.played / duration}
+    }
+    // True once an animation that plays once reached its last frame
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+    // The event fired by the last update, if playback reached a frame that has one
+    pub fn event(&self) -> Option<AnimEvent> {
+        self.event
+    }
+    pub fn update_frame(&mut self, assets: &Assets, dt: f32){
+        self.event = None;
+        if self.finished {return}
+        self.played += dt * self.speed;
+        self.frame_elapsed += dt * self.speed;
+        loop {
+            let frame_time = assets.get_anim_frame_time(self.asset_tag, self.current_frame).unwrap_or(0.1).max(MIN_FRAME_TIME);
+            if self.frame_elapsed < frame_time {break}
+            self.frame_elapsed -= frame_time;
+            if !self.advance() {
+                self.frame_elapsed = 0.;
+                break
+            }
+            if let Some(event) = assets.get_anim_event(self.asset_tag, self.current_frame) {
+                self.event = Some(event);
+            }
         }
     }
-    pub fn update_frame(&mut self, time: f32){
-        if time < self.next_frame_upd {return}
-        self.current_frame = (self.current_frame + 1) % self.len;
-        self.next_frame_upd = time + self.frame_time;
+    // Moves to the next frame. False if there's none because the animation ended.
+    fn advance(&mut self) -> bool {
+        let last = self.len - 1;
+        match self.loop_mode {
+            LoopMode::Loop => self.current_frame = (self.current_frame + 1) % self.len,
+            LoopMode::Once => {
+                if self.current_frame >= last {
+                    self.finished = true;
+                    return false;
+                }
+                self.current_frame += 1;
+            }
+            LoopMode::PingPong => {
+                if last == 0 {return true}
+                if self.current_frame >= last {self.backwards = true}
+                if self.current_frame == 0 {self.backwards = false}
+                self.current_frame = if self.backwards {self.current_frame - 1} else {self.current_frame + 1};
+            }
+        }
+        true
     }
 }
 
 impl Update for Animation{
-    fn update(ecs: &mut ECS, _assets: &Assets, _rng: &mut Rand32, entity_id: usize, time: f32, _dt: f32) {
+    fn update(ecs: &mut ECS, assets: &Assets, _rng: &mut Rand32, entity_id: usize, _time: f32, dt: f32) {
         let mut anim = ecs.get_component::<Animation>(entity_id).unwrap();
-        anim.update_frame(time);
+        anim.refresh(assets);
+        anim.update_frame(assets, dt);
         ecs.set_component(entity_id, anim);
     }
 }
 
-impl Draw for Animation{
-    fn draw(&self, ctx: &mut Context, ecs: &ECS, assets: &Assets, entity_id: usize, pos: Vec2, screen_size: Screen2) -> GameResult {
-        assets.get_anim_frame(self.asset_tag, self.current_frame).unwrap().draw(ctx, ecs, assets, entity_id, pos, screen_size)
+impl Animation {
+    // A hot reload can change the clip between `refresh` and drawing, so the frame may be past
+    // its end or gone. Missing clips draw as the placeholder.
+    fn frame_sprite(&self, assets: &Assets) -> Sprite {
+        let mut sprite = assets.get_anim(self.asset_tag)
+            .and_then(|anim| anim.frames.get(self.current_frame).or(anim.frames.last()))
+            .copied()
+            .unwrap_or(Sprite::new(self.asset_tag));
+        sprite.set_transform(self.transform);
+        sprite
+    }
+    pub fn draw_batched(&self, ctx: &mut Context, assets: &Assets, batcher: &mut SpriteBatcher, pos: Vec2, screen_size: Screen2) -> GameResult {
+        if !self.visible {return Ok(())}
+        self.frame_sprite(assets).draw_batched(ctx, assets, batcher, pos, screen_size)
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub struct AnimStateMachine<State: 'static + Copy + Clone + PartialEq>{
-    asset_tag: AssetTag,
-    current_state: State,
-    current_anim_tag: AssetTag,
-    current_anim: Animation,
+impl Draw for Animation{
+    fn draw(&self, ctx: &mut Context, ecs: &ECS, assets: &Assets, entity_id: usize, pos: Vec2, screen_size: Screen2) -> GameResult {
+        if !self.visible {return Ok(())}
+        self.frame_sprite(assets).draw(ctx, ecs, assets, entity_id, pos, screen_size)
+    }
 }
 
-impl<State: 'static + Copy + Clone + PartialEq> AnimStateMachine<State> {
-    pub fn new(assets: &Assets, asset_tag: AssetTag, start_state: State) -> AnimStateMachine<State> {
-        let current_anim_tag = assets.get_state_machine_anim(asset_tag, start_state).unwrap();
-        AnimStateMachine {
-            asset_tag,
-            current_state: start_state,
-            current_anim_tag,
-            current_anim: Animation::new(assets, current_anim_tag),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn frames_played(loop_mode: LoopMode, len: usize, steps: usize) -> Vec<usize> {
+        let mut anim = Animation {
+            asset_tag: AssetTag::new("test"),
+            len,
+            loop_mode,
+            current_frame: 0,
+            frame_elapsed: 0.,
+            backwards: false,
+            finished: false,
+            speed: 1.,
+            scale_with_scroll: false,
+            event: None,
+            played: 0.,
+            transform: SpriteTransform::default(),
+            visible: true,
+        };
+        let mut frames = vec![anim.current_frame];
+        for _ in 0..steps {
+            anim.advance();
+            frames.push(anim.current_frame);
         }
+        frames
     }
-    pub fn update_state(&mut self, ecs: &mut ECS, assets: &Assets, entity_id: usize) {
-        let new_state = ecs.get_component::<State>(entity_id);//.unwrap();
-        if new_state == None { return }
-        let new_state = new_state.unwrap();
-        if self.current_state == new_state { return }
-        self.current_state = new_state;
-        let anim_tag = assets.get_state_machine_anim(self.asset_tag, new_state).unwrap();
-        ecs.set_component::<Animation>(entity_id, Animation::new(assets, anim_tag));
+    #[test]
+    fn loop_modes(){
+        assert_eq!(frames_played(LoopMode::Loop, 3, 4), [0, 1, 2, 0, 1]);
+        assert_eq!(frames_played(LoopMode::Once, 3, 4), [0, 1, 2, 2, 2]);
+        assert_eq!(frames_played(LoopMode::PingPong, 3, 6), [0, 1, 2, 1, 0, 1, 2]);
+        assert_eq!(frames_played(LoopMode::PingPong, 1, 2), [0, 0, 0]);
     }
 }
-
-impl<State: 'static + Copy + Clone + PartialEq> Update for AnimStateMachine<State>{
-    fn update(ecs: &mut ECS, assets: &Assets, _rng: &mut Rand32, entity_id: usize, _time: f32, _dt: f32) {
-        let mut anim = ecs.get_component::<AnimStateMachine<State>>(entity_id).unwrap();
-        anim.update_state(ecs, assets, entity_id);
-        ecs.set_component(entity_id, anim);
-    }
-}
\ No newline at end of file
@@ -0,0 +1,39 @@
+use crate::prelude::*;
+use collision::BoundType;
+
+// Keeps hopping off the ground, falling with the entity's `Movable` gravity in between
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounce{
+    velocity: f32,
+}
+
+impl Bounce{
+    pub fn new(velocity: f32) -> Bounce{
+        Bounce{
+            velocity,
+        }
+    }
+    // Height and vertical velocity after landing, given the collider's lowest point offset
+    pub fn land(&self, y: f32, velocity: f32, lowest_point_offs: f32) -> (f32, f32) {
+        if y + lowest_point_offs >= GROUND_Y_COORD {return (y, velocity)}
+        (GROUND_Y_COORD - lowest_point_offs, self.velocity)
+    }
+    // Puts a freshly spawned entity on the ground, partway into a hop
+    pub fn launch(ecs: &mut ECS, rng: &mut Rand32, entity_id: usize, velocity: f32, gravity: f32) {
+        let bounce = Bounce::new(velocity);
+        let lowest_point_offs = ecs.get_component::<Collider>(entity_id).unwrap().get_bound_offset(BoundType::Down).y;
+        let mut mov = ecs.get_component::<Movable>(entity_id).unwrap();
+        mov.pos.y = GROUND_Y_COORD - lowest_point_offs;
+        mov.velocity.y = velocity * (0.4 + 0.6 * rng.rand_float());
+        mov.gravity.y = gravity;
+        ecs.set_component(entity_id, mov);
+        ecs.set_component(entity_id, bounce);
+    }
+    pub fn bounce_off_ground(ecs: &mut ECS, entity_id: usize) {
+        let bounce = ecs.get_component::<Bounce>(entity_id).unwrap();
+        let lowest_point_offs = ecs.get_component::<Collider>(entity_id).unwrap().get_bound_offset(BoundType::Down).y;
+        let mut mov = ecs.get_component::<Movable>(entity_id).unwrap();
+        (mov.pos.y, mov.velocity.y) = bounce.land(mov.pos.y, mov.velocity.y, lowest_point_offs);
+        ecs.set_component(entity_id, mov);
+    }
+}
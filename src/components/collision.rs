@@ -21,6 +21,19 @@ impl BoxCollider{
         new_collider
     }
 
+    pub fn grown(&self, amount: f32) -> BoxCollider{
+        let mut new_collider = *self;
+        new_collider.half_size += v2!(amount, amount);
+        new_collider
+    }
+
+    // Scaled around its center, mirrored scales included
+    pub fn scaled(&self, scale: Vec2) -> BoxCollider{
+        let mut new_collider = *self;
+        new_collider.half_size *= scale.abs();
+        new_collider
+    }
+
     pub fn contains_point(&self, pos: Vec2, point: Vec2) -> bool {
         point.x >= pos.x - self.half_size.x &&
         point.x <= pos.x + self.half_size.x &&
@@ -120,6 +133,13 @@ impl Collider {
         }
     }
 
+    // Every box grown by `amount` on each side
+    pub fn grown(&self, amount: f32) -> Collider {
+        Collider {
+            col: self.col.map(|c| c.map(|c| c.grown(amount))),
+        }
+    }
+
     fn get_pos(ecs: &ECS, entity_id: usize) -> Vec2{
         ecs.get_component::<Movable>(entity_id).unwrap().pos
     }
@@ -133,13 +153,17 @@ impl Collider {
         let col2 = col2.unwrap();
         let pos1 = Collider::get_pos(ecs, entity1);
         let pos2 = Collider::get_pos(ecs, entity2);
-        for c1 in col1.col{
+        col1.check_collision(pos1, &col2, pos2)
+    }
+
+    pub fn check_collision(&self, pos: Vec2, other: &Collider, other_pos: Vec2) -> bool {
+        for c1 in self.col{
             if c1 == None {continue}
             let c1 = c1.unwrap();
-            for c2 in col2.col{
+            for c2 in other.col{
                 if c2 == None {continue}
                 let c2 = c2.unwrap();
-                if BoxCollider::check_collision(c1, c2, pos1, pos2) {
+                if BoxCollider::check_collision(c1, c2, pos, other_pos) {
                     return true;
                 }
             }
@@ -219,6 +243,12 @@ mod tests {
     use super::*;
     use crate::v2;
     #[test]
+    fn scaled_contains_point(){
+        let box_col = BoxCollider::new(v2!(10., 5.)).scaled(v2!(-2., 0.5));
+        assert!(box_col.contains_point(v2!(), v2!(-19., 2.)));
+        assert!(!box_col.contains_point(v2!(), v2!(0., 3.)));
+    }
+    #[test]
     fn bound_test(){
         let box_col = BoxCollider::new(v2!(10., 10.)).with_offset(v2!(-5., 0.));
         let col = Collider::new_single(box_col);
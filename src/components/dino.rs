@@ -1,29 +1,49 @@
 use crate::prelude::*;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum DinoState{
-    Run, Jump, Dead,
-}
-
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct DinoController {
     entity_id: usize,
     jump_sound_tag: AssetTag,
+    jump_velocity: f32,
+    // Jumps allowed in mid-air, and how many are left before landing
+    air_jumps: u32,
+    air_jumps_left: u32,
 }
 
 impl DinoController {
-    pub fn new(entity_id: usize, jump_sound_tag: AssetTag) -> DinoController {
+    pub fn new(entity_id: usize, jump_sound_tag: AssetTag, jump_velocity: f32) -> DinoController {
         DinoController {
             entity_id,
             jump_sound_tag,
+            jump_velocity,
+            air_jumps: 0,
+            air_jumps_left: 0,
         }
     }
-    pub fn jump(&self, ctx: &Context, ecs: &mut ECS, assets: &mut Assets){
+    pub fn collider() -> Collider {
+        let body = BoxCollider::new(v2!(14., 25.)).with_offset(v2!(-6., -18.));
+        let head = BoxCollider::new(v2!(22., 17.)).with_offset(v2!(18., 32.));
+        Collider::new_double(body, head)
+    }
+    pub fn set_jump_velocity(&mut self, jump_velocity: f32){
+        self.jump_velocity = jump_velocity;
+    }
+    pub fn set_air_jumps(&mut self, air_jumps: u32){
+        self.air_jumps = air_jumps;
+        self.air_jumps_left = self.air_jumps_left.min(air_jumps);
+    }
+    pub fn jump(&mut self, ctx: &Context, ecs: &mut ECS, assets: &mut Assets){
         let mut mov: Movable = ecs.get_component(self.entity_id).unwrap();
-        let jump_success = mov.jump(JUMP_VELOCITY);
+        let mut jump_success = mov.jump(self.jump_velocity);
+        if !jump_success && self.air_jumps_left > 0 {
+            self.air_jumps_left -= 1;
+            mov.velocity.y = self.jump_velocity;
+            jump_success = true;
+        }
         if jump_success {
             ecs.set_component(self.entity_id, mov);
-            let _ = assets.get_audio_mut(self.jump_sound_tag).unwrap().play(ctx);
+            ecs.set_component(self.entity_id, *self);
+            let _ = assets.get_audio_mut(self.jump_sound_tag).play(ctx);
         }
     }
 }
@@ -31,6 +51,13 @@ impl DinoController {
 impl Update for DinoController{
     fn update(ecs: &mut ECS, _assets: &Assets, _rng: &mut Rand32, entity_id: usize, _time: f32, _dt: f32) {
         let mov: Movable = ecs.get_component(entity_id).unwrap();
-        ecs.set_component::<DinoState>(entity_id, if mov.on_ground {DinoState::Run} else {DinoState::Jump});
+        let mut params = ecs.get_component::<AnimParams>(entity_id).unwrap_or_default();
+        params.set_bool(AnimParam::ON_GROUND, mov.on_ground);
+        ecs.set_component(entity_id, params);
+        if mov.on_ground {
+            let mut controller = ecs.get_component::<DinoController>(entity_id).unwrap();
+            controller.air_jumps_left = controller.air_jumps;
+            ecs.set_component(entity_id, controller);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,69 @@
+use crate::prelude::*;
+
+// How many times per second an invulnerable entity blinks
+const FLASH_RATE: f32 = 10.0;
+
+// Lives, and a short invulnerability window after every hit that isn't fatal
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Health{
+    lives: u32,
+    max_lives: u32,
+    invulnerability_time: f32,
+    invulnerable_for: f32,
+}
+
+impl Health{
+    pub fn new(lives: u32, invulnerability_time: f32) -> Health{
+        Health{
+            lives,
+            max_lives: lives,
+            invulnerability_time,
+            invulnerable_for: 0.,
+        }
+    }
+    pub fn lives(&self) -> u32 {
+        self.lives
+    }
+    pub fn max_lives(&self) -> u32 {
+        self.max_lives
+    }
+    pub fn invulnerable(&self) -> bool {
+        self.invulnerable_for > 0.
+    }
+    // Returns whether the entity survived the hit
+    pub fn take_hit(&mut self) -> bool {
+        self.lives = self.lives.saturating_sub(1);
+        if self.lives == 0 {return false}
+        self.invulnerable_for = self.invulnerability_time;
+        true
+    }
+    pub fn visible(&self) -> bool {
+        !self.invulnerable() || (self.invulnerable_for * FLASH_RATE).fract() < 0.5
+    }
+}
+
+impl Update for Health{
+    fn update(ecs: &mut ECS, _assets: &Assets, _rng: &mut Rand32, entity_id: usize, _time: f32, dt: f32) {
+        let mut health = ecs.get_component::<Health>(entity_id).unwrap();
+        health.invulnerable_for = (health.invulnerable_for - dt).max(0.);
+        ecs.set_component(entity_id, health);
+        if let Some(mut anim) = ecs.get_component::<Animation>(entity_id) {
+            anim.set_visible(health.visible());
+            ecs.set_component(entity_id, anim);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn last_life_is_fatal(){
+        let mut health = Health::new(2, 1.);
+        assert!(health.take_hit());
+        assert!(health.invulnerable());
+        assert_eq!(health.lives(), 1);
+        assert!(!health.take_hit());
+        assert!(!Health::new(1, 1.).take_hit());
+    }
+}
@@ -3,13 +3,23 @@ pub mod ezshape;
 pub mod collision;
 pub mod sprite;
 pub mod animation;
+pub mod anim_state_machine;
 pub mod dino;
 pub mod ptero;
+pub mod bounce;
+pub mod health;
+pub mod render_layer;
+pub mod tween;
 
 pub use movable::{Movable, EndlessScroll};
 pub use ezshape::CircleGraphic;
 pub use collision::{Collider, BoxCollider};
-pub use sprite::Sprite;
-pub use animation::{Animation, AnimStateMachine};
-pub use dino::{DinoState, DinoController};
-pub use ptero::Ptero;
+pub use sprite::{Sprite, SpriteTransform};
+pub use animation::{Animation, AnimEvent, LoopMode};
+pub use anim_state_machine::{AnimParam, AnimParams, AnimStateMachine, StateMachineData, StateMachineDef};
+pub use dino::DinoController;
+pub use ptero::{Ptero, FlightPattern, PTERO_LANES};
+pub use bounce::Bounce;
+pub use health::Health;
+pub use render_layer::{RenderLayer, draw_entities};
+pub use tween::{Ease, Tween, TweenEvent, TweenTarget, Tweens, run_tweens};
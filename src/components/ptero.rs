@@ -1,35 +1,105 @@
-use crate::collision::BoundType;
 use crate::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::{PI, TAU};
+
+// Heights above the ground pteros fly at when nothing else is asked for
+pub const PTERO_LANES: [f32; 3] = [35., 75., 115.];
+
+// How a ptero's height changes as it crosses the screen.
+// Offsets depend only on the x position, so the fair spawn check can predict them.
+#[derive(Copy, Clone, Debug, PartialEq, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FlightPattern {
+    #[default]
+    Straight,
+    // Bobs up and down around its lane
+    SineBob { amplitude: f32, wavelength: f32 },
+    // Dives `depth` pixels down around `x` and climbs back up, `width` pixels to each side
+    Swoop { depth: f32, x: f32, width: f32 },
+    // Moves `delta` pixels up (or down if negative) over `width` pixels after passing `x`
+    HeightChange { delta: f32, x: f32, width: f32 },
+}
+
+impl FlightPattern {
+    pub fn offset(&self, x: f32) -> f32 {
+        match *self {
+            FlightPattern::Straight => 0.,
+            FlightPattern::SineBob { amplitude, wavelength } => {
+                if wavelength <= 0. {return 0.}
+                amplitude * (TAU * x / wavelength).sin()
+            }
+            FlightPattern::Swoop { depth, x: center, width } => {
+                if width <= 0. || (x - center).abs() >= width {return 0.}
+                -depth * 0.5 * (1. + (PI * (x - center) / width).cos())
+            }
+            FlightPattern::HeightChange { delta, x: start, width } => {
+                // Pteros fly left, so progress grows as x goes down
+                let t =
+                    if width > 0. {((start - x) / width).clamp(0., 1.)}
+                    else if x <= start {1.}
+                    else {0.};
+                delta * t * t * (3. - 2. * t)
+            }
+        }
+    }
+}
 
 #[derive(Copy, Clone, Debug, PartialEq, Default)]
 pub struct Ptero{
-    needs_update: bool,
+    base_y: f32,
+    pattern: FlightPattern,
 }
 
 impl Ptero{
     pub fn new() -> Ptero{
         Ptero{
-            needs_update: true,
+            base_y: GROUND_Y_COORD + PTERO_LANES[1],
+            pattern: FlightPattern::Straight,
         }
     }
+    pub fn random_lane(rng: &mut Rand32) -> f32 {
+        PTERO_LANES[rng.rand_u32() as usize % PTERO_LANES.len()]
+    }
+    pub fn base_y(&self) -> f32 {
+        self.base_y
+    }
+    pub fn height_at(&self, x: f32) -> f32 {
+        self.base_y + self.pattern.offset(x)
+    }
+    // Sets the lane and pattern of a freshly spawned ptero
+    pub fn launch(ecs: &mut ECS, entity_id: usize, height: f32, pattern: FlightPattern) {
+        let ptero = Ptero {
+            base_y: GROUND_Y_COORD + height,
+            pattern,
+        };
+        ecs.set_component(entity_id, ptero);
+        Ptero::follow_pattern(ecs, entity_id);
+    }
+    pub fn follow_pattern(ecs: &mut ECS, entity_id: usize) {
+        let ptero = ecs.get_component::<Ptero>(entity_id).unwrap();
+        let mut mov = ecs.get_component::<Movable>(entity_id).unwrap();
+        mov.pos.y = ptero.height_at(mov.pos.x);
+        ecs.set_component(entity_id, mov);
+    }
 }
 
 impl Update for Ptero{
-    fn update(ecs: &mut ECS, _assets: &Assets, rng: &mut Rand32, entity_id: usize, _time: f32, _dt: f32) {
-        let rightmost_x = ecs.get_component::<Collider>(entity_id).unwrap().get_bound(ecs, entity_id, BoundType::Right).x;
-        let mut ptero = ecs.get_component::<Ptero>(entity_id).unwrap();
-        if rightmost_x > - SCREEN.0 / 2.{
-            if !ptero.needs_update {
-                ptero.needs_update = true;
-                ecs.set_component(entity_id, ptero);
-            }
-            return
-        }
-        if !ptero.needs_update {return}
-        let mut mov = ecs.get_component::<Movable>(entity_id).unwrap();
-        mov.pos.y = GROUND_Y_COORD + (rng.rand_u32() % 3) as f32 * 40. + 35.;
-        ptero.needs_update = false;
-        ecs.set_component(entity_id, ptero);
-        ecs.set_component(entity_id, mov);
+    fn update(ecs: &mut ECS, _assets: &Assets, _rng: &mut Rand32, entity_id: usize, _time: f32, _dt: f32) {
+        Ptero::follow_pattern(ecs, entity_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn patterns_return_to_lane(){
+        let swoop = FlightPattern::Swoop { depth: 40., x: 0., width: 100. };
+        assert_eq!(swoop.offset(0.), -40.);
+        assert_eq!(swoop.offset(150.), 0.);
+        let change = FlightPattern::HeightChange { delta: 40., x: 100., width: 50. };
+        assert_eq!(change.offset(200.), 0.);
+        assert_eq!(change.offset(75.), 20.);
+        assert_eq!(change.offset(-300.), 40.);
     }
-}
\ No newline at end of file
+}
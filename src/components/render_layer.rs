@@ -0,0 +1,67 @@
+use crate::prelude::*;
+use std::ops::RangeBounds;
+
+// Draw order of an entity. Lower layers are drawn first, so higher ones end up on top.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RenderLayer(pub i32);
+
+impl RenderLayer {
+    pub const GROUND:     RenderLayer = RenderLayer(0);
+    pub const OBSTACLES:  RenderLayer = RenderLayer(100);
+    pub const PICKUPS:    RenderLayer = RenderLayer(150);
+    pub const DINO:       RenderLayer = RenderLayer(200);
+    pub const PARTICLES:  RenderLayer = RenderLayer(300);
+    pub const FOREGROUND: RenderLayer = RenderLayer(500);
+
+    // Scenery below 0 goes behind the ground, the rest in front of everything but the UI
+    pub fn scenery(z: i32) -> RenderLayer {
+        if z < 0 {RenderLayer(z)} else {RenderLayer(RenderLayer::FOREGROUND.0 + z)}
+    }
+}
+
+// Draws every entity in the given layers that has a position and a sprite or an animation.
+// They're sorted by layer, then from the top of the screen down, then by creation order,
+// so the result doesn't depend on which components an entity has.
+// Sprites from the atlas are batched; returns the number of draw calls it took.
+pub fn draw_entities(ctx: &mut Context, ecs: &ECS, assets: &Assets, screen_size: Screen2, layers: impl RangeBounds<RenderLayer>) -> GameResult<usize> {
+    let mut order: Vec<(RenderLayer, f32, usize)> = Vec::new();
+    if let Some(movables) = ecs.borrow_component_vec::<Movable>() {
+        for (id, mov) in movables.iter().enumerate() {
+            if let Some(mov) = mov {
+                if ecs.get_component::<Sprite>(id).is_none() && ecs.get_component::<Animation>(id).is_none() {continue}
+                let layer = ecs.get_component::<RenderLayer>(id).unwrap_or_default();
+                if !layers.contains(&layer) {continue}
+                order.push((layer, mov.pos.y, id));
+            }
+        }
+    }
+    order.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)).then(a.2.cmp(&b.2)));
+
+    let mut batcher = SpriteBatcher::new(assets);
+    for (_, _, id) in order {
+        let pos = ecs.get_component::<Movable>(id).unwrap().pos;
+        if let Some(sprite) = ecs.get_component::<Sprite>(id) {
+            sprite.draw_batched(ctx, assets, &mut batcher, pos, screen_size)?;
+        }
+        if let Some(anim) = ecs.get_component::<Animation>(id) {
+            // During a crossfade the old clip is drawn under the new one
+            if let Some(fading) = ecs.get_component::<AnimStateMachine>(id).and_then(|machine| machine.fading()) {
+                fading.draw_batched(ctx, assets, &mut batcher, pos, screen_size)?;
+            }
+            anim.draw_batched(ctx, assets, &mut batcher, pos, screen_size)?;
+        }
+    }
+    batcher.flush(ctx)?;
+    Ok(batcher.draw_calls())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn scenery_layers_wrap_the_world(){
+        assert!(RenderLayer::scenery(-10) < RenderLayer::GROUND);
+        assert!(RenderLayer::scenery(-30) < RenderLayer::scenery(-10));
+        assert!(RenderLayer::scenery(0) > RenderLayer::PARTICLES);
+    }
+}
@@ -1,9 +1,48 @@
 use crate::prelude::*;
 
+// How a sprite is drawn besides where: mirrored, turned, stretched and colored.
+// Turning and scaling happen around the sprite's anchor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteTransform{
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // Radians, clockwise on screen
+    pub rotation: f32,
+    pub scale: Vec2,
+    // Multiplied with the image's colors
+    pub tint: Color,
+    pub alpha: f32,
+}
+
+impl Default for SpriteTransform{
+    fn default() -> Self {
+        SpriteTransform{
+            flip_x: false,
+            flip_y: false,
+            rotation: 0.,
+            scale: v2!(1., 1.),
+            tint: Color::WHITE,
+            alpha: 1.,
+        }
+    }
+}
+
+impl SpriteTransform{
+    pub fn apply(&self, param: graphics::DrawParam) -> graphics::DrawParam {
+        let flip = v2!(if self.flip_x {-1.} else {1.}, if self.flip_y {-1.} else {1.});
+        let tint = self.tint;
+        param
+            .rotation(self.rotation)
+            .scale(self.scale * flip)
+            .color(Color::new(tint.r, tint.g, tint.b, tint.a * self.alpha))
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Sprite{
     asset_tag: AssetTag,
     offset: Vec2,
+    transform: SpriteTransform,
 }
 
 impl Sprite{
@@ -11,25 +50,64 @@ impl Sprite{
         Sprite{
             asset_tag,
             offset: v2!(0.5, 0.5),
+            transform: SpriteTransform::default(),
         }
     }
     pub fn set_offset(&mut self, offset: Vec2){
         self.offset = offset;
     }
+    pub fn transform(&self) -> SpriteTransform {
+        self.transform
+    }
+    pub fn transform_mut(&mut self) -> &mut SpriteTransform {
+        &mut self.transform
+    }
+    pub fn set_transform(&mut self, transform: SpriteTransform){
+        self.transform = transform;
+    }
     pub fn set_tag(&mut self, tag: AssetTag){
         self.asset_tag = tag;
     }
-}
-
-impl Draw for Sprite{
-    fn draw(&self, ctx: &mut Context, _ecs: &ECS, assets: &Assets, _entity_id: usize, pos: Vec2, screen_size: Screen2) -> GameResult{
+    pub fn tag(&self) -> AssetTag {
+        self.asset_tag
+    }
+    fn draw_param(&self, pos: Vec2, screen_size: Screen2) -> graphics::DrawParam {
         let mut pos = world_to_screen_coords(screen_size, pos);
         // Floor the coordinates to prevent blurring
         pos.x = pos.x.floor();
         pos.y = pos.y.floor();
-        let draw_params = graphics::DrawParam::new()
+        self.transform.apply(graphics::DrawParam::new()
             .dest(pos)
-            .offset(self.offset);
-        graphics::draw(ctx, assets.get_image(self.asset_tag).unwrap(), draw_params)
+            .offset(self.offset))
+    }
+    pub fn draw_batched(&self, ctx: &mut Context, assets: &Assets, batcher: &mut SpriteBatcher, pos: Vec2, screen_size: Screen2) -> GameResult {
+        batcher.draw(ctx, assets, self.asset_tag, self.draw_param(pos, screen_size))
+    }
+}
+
+impl Draw for Sprite{
+    fn draw(&self, ctx: &mut Context, _ecs: &ECS, assets: &Assets, _entity_id: usize, pos: Vec2, screen_size: Screen2) -> GameResult{
+        graphics::draw(ctx, assets.get_image(self.asset_tag), self.draw_param(pos, screen_size))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ggez::graphics::Transform;
+    #[test]
+    fn transform_sets_draw_param(){
+        let transform = SpriteTransform {
+            flip_x: true,
+            scale: v2!(2., 3.),
+            tint: Color::new(1., 0.5, 0.5, 0.8),
+            alpha: 0.5,
+            ..SpriteTransform::default()
+        };
+        let param = transform.apply(graphics::DrawParam::new());
+        assert_eq!(param.color, Color::new(1., 0.5, 0.5, 0.4));
+        match param.trans {
+            Transform::Values { scale, .. } => assert_eq!((scale.x, scale.y), (-2., 3.)),
+            Transform::Matrix(_) => panic!("Expected plain values"),
+        }
+    }
+}
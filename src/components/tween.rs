@@ -0,0 +1,315 @@
+// Values that move from one number to another over time, following an easing curve.
+// A `Tween` works on its own, for things that aren't entities like the UI. The `Tweens`
+// component runs them on an entity's position, alpha, scale or rotation.
+use crate::prelude::*;
+use std::f32::consts::PI;
+
+// Tweens past this many on one entity can't be added
+const MAX_TWEENS: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Ease {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+}
+
+fn elastic_in(t: f32) -> f32 {
+    if t <= 0. || t >= 1. {return t}
+    -(2f32).powf(10. * t - 10.) * ((t * 10. - 10.75) * (2. * PI / 3.)).sin()
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1. / D {N * t * t}
+    else if t < 2. / D {let t = t - 1.5 / D; N * t * t + 0.75}
+    else if t < 2.5 / D {let t = t - 2.25 / D; N * t * t + 0.9375}
+    else {let t = t - 2.625 / D; N * t * t + 0.984375}
+}
+
+impl Ease {
+    // Maps progress from 0 to 1 onto the curve. Elastic curves overshoot in between.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        // Out is In played backwards, InOut is In then Out, each over half the time
+        let out = |ease_in: fn(f32) -> f32| 1. - ease_in(1. - t);
+        let in_out = |ease_in: fn(f32) -> f32| {
+            if t < 0.5 {ease_in(t * 2.) / 2.} else {1. - ease_in(2. - t * 2.) / 2.}
+        };
+        let quad: fn(f32) -> f32 = |t| t * t;
+        let cubic: fn(f32) -> f32 = |t| t * t * t;
+        let bounce_in: fn(f32) -> f32 = |t| 1. - bounce_out(1. - t);
+        match self {
+            Ease::Linear       => t,
+            Ease::QuadIn       => quad(t),
+            Ease::QuadOut      => out(quad),
+            Ease::QuadInOut    => in_out(quad),
+            Ease::CubicIn      => cubic(t),
+            Ease::CubicOut     => out(cubic),
+            Ease::CubicInOut   => in_out(cubic),
+            Ease::ElasticIn    => elastic_in(t),
+            Ease::ElasticOut   => out(elastic_in),
+            Ease::ElasticInOut => in_out(elastic_in),
+            Ease::BounceIn     => bounce_in(t),
+            Ease::BounceOut    => bounce_out(t),
+            Ease::BounceInOut  => in_out(bounce_in),
+        }
+    }
+}
+
+// Fired when a tween finishes. Named like animation events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TweenEvent(AssetTag);
+
+impl TweenEvent {
+    pub const fn new(name: &str) -> TweenEvent {
+        TweenEvent(AssetTag::new(name))
+    }
+
+    pub const PICKUP_COLLECTED: TweenEvent = TweenEvent::new("pickup_collected");
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tween {
+    from: f32,
+    to: f32,
+    // Seconds
+    duration: f32,
+    elapsed: f32,
+    ease: Ease,
+    event: Option<TweenEvent>,
+}
+
+impl Tween {
+    pub fn new(from: f32, to: f32, duration: f32, ease: Ease) -> Tween {
+        Tween {
+            from,
+            to,
+            duration: duration.max(0.),
+            elapsed: 0.,
+            ease,
+            event: None,
+        }
+    }
+    // Already at its end
+    pub fn done(value: f32) -> Tween {
+        Tween::new(value, value, 0., Ease::Linear)
+    }
+    pub fn with_event(mut self, event: TweenEvent) -> Tween {
+        self.event = Some(event);
+        self
+    }
+    pub fn value(&self) -> f32 {
+        let t = if self.duration > 0. {self.elapsed / self.duration} else {1.};
+        self.from + (self.to - self.from) * self.ease.apply(t)
+    }
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+    // Returns true on the update it finishes
+    pub fn update(&mut self, dt: f32) -> bool {
+        if self.finished() {return false}
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        self.finished()
+    }
+}
+
+// What a `Tweens` component moves
+#[derive(Copy, Clone, Debug)]
+pub enum TweenTarget {
+    PosX,
+    PosY,
+    // These apply to the entity's `Sprite` and `Animation`, whichever it has
+    Alpha,
+    Scale,
+    Rotation,
+    // Anything else, set by the given function
+    Custom(fn(&mut ECS, usize, f32)),
+}
+
+impl TweenTarget {
+    pub fn apply(self, ecs: &mut ECS, entity_id: usize, value: f32) {
+        let set_transform = |ecs: &mut ECS, set: &dyn Fn(&mut SpriteTransform)| {
+            if let Some(mut sprite) = ecs.get_component::<Sprite>(entity_id) {
+                set(sprite.transform_mut());
+                ecs.set_component(entity_id, sprite);
+            }
+            if let Some(mut anim) = ecs.get_component::<Animation>(entity_id) {
+                set(anim.transform_mut());
+                ecs.set_component(entity_id, anim);
+            }
+        };
+        match self {
+            TweenTarget::PosX | TweenTarget::PosY => {
+                let Some(mut mov) = ecs.get_component::<Movable>(entity_id) else {return};
+                if matches!(self, TweenTarget::PosX) {mov.pos.x = value} else {mov.pos.y = value}
+                ecs.set_component(entity_id, mov);
+            }
+            TweenTarget::Alpha     => set_transform(ecs, &|transform| transform.alpha = value),
+            TweenTarget::Scale     => set_transform(ecs, &|transform| transform.scale = Vec2::splat(value)),
+            TweenTarget::Rotation  => set_transform(ecs, &|transform| transform.rotation = value),
+            TweenTarget::Custom(set) => set(ecs, entity_id, value),
+        }
+    }
+}
+
+// Tweens running on an entity. The ones added together run at the same time;
+// `then()` starts a new step that waits for everything before it to finish.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tweens {
+    // (target, tween, step)
+    slots: [Option<(TweenTarget, Tween, u32)>; MAX_TWEENS],
+    last_step: u32,
+    // Fired during the last update, one per tween that finished
+    events: [Option<TweenEvent>; MAX_TWEENS],
+}
+
+impl Tweens {
+    pub fn new() -> Tweens {
+        Tweens::default()
+    }
+    // Tweens past `MAX_TWEENS` are reported and dropped
+    pub fn with(mut self, target: TweenTarget, tween: Tween) -> Tweens {
+        match self.slots.iter().position(|slot| slot.is_none()) {
+            Some(slot) => self.slots[slot] = Some((target, tween, self.last_step)),
+            None => eprintln!("Dropped a tween, an entity can only run {MAX_TWEENS} at once"),
+        }
+        self
+    }
+    pub fn then(mut self) -> Tweens {
+        self.last_step += 1;
+        self
+    }
+    pub fn finished(&self) -> bool {
+        self.slots.iter().all(|slot| slot.is_none())
+    }
+    // The completion events fired by the last update
+    pub fn events(&self) -> impl Iterator<Item = TweenEvent> + '_ {
+        self.events.iter().flatten().copied()
+    }
+    pub fn fired(&self, event: TweenEvent) -> bool {
+        self.events().any(|fired| fired == event)
+    }
+    fn step(&self) -> Option<u32> {
+        self.slots.iter().flatten().map(|(_, _, step)| *step).min()
+    }
+    // Moves the current step's tweens on and sets their targets
+    pub fn update_tweens(&mut self, ecs: &mut ECS, entity_id: usize, dt: f32) {
+        self.events = [None; MAX_TWEENS];
+        let Some(step) = self.step() else {return};
+        for (slot, event) in self.slots.iter_mut().zip(self.events.iter_mut()) {
+            let Some((target, mut tween, tween_step)) = *slot else {continue};
+            if tween_step != step {continue}
+            tween.update(dt);
+            target.apply(ecs, entity_id, tween.value());
+            if tween.finished() {
+                *event = tween.event;
+                *slot = None;
+            } else {
+                *slot = Some((target, tween, tween_step));
+            }
+        }
+    }
+}
+
+// Runs every `Tweens` in the ECS
+pub fn run_tweens(ecs: &mut ECS, dt: f32) {
+    let ids: Vec<usize> = match ecs.borrow_component_vec::<Tweens>() {
+        Some(tweens) => tweens.iter().enumerate().filter(|(_, t)| t.is_some()).map(|(id, _)| id).collect(),
+        None => return,
+    };
+    for id in ids {
+        let mut tweens = ecs.get_component::<Tweens>(id).unwrap();
+        tweens.update_tweens(ecs, id, dt);
+        ecs.set_component(id, tweens);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn eases_start_and_end_in_place(){
+        let eases = [
+            Ease::Linear, Ease::QuadIn, Ease::QuadOut, Ease::QuadInOut, Ease::CubicIn, Ease::CubicOut, Ease::CubicInOut,
+            Ease::ElasticIn, Ease::ElasticOut, Ease::ElasticInOut, Ease::BounceIn, Ease::BounceOut, Ease::BounceInOut,
+        ];
+        for ease in eases {
+            assert!(ease.apply(0.).abs() < 1e-4, "{ease:?}");
+            assert!((ease.apply(1.) - 1.).abs() < 1e-4, "{ease:?}");
+        }
+        assert_eq!(Ease::QuadIn.apply(0.5), 0.25);
+        assert_eq!(Ease::QuadOut.apply(0.5), 0.75);
+        assert!(Ease::ElasticOut.apply(0.2) > 1.);
+    }
+    #[test]
+    fn tweens_chain_and_run_in_parallel(){
+        let mut ecs = ECS::new();
+        let id = ecs.new_entity();
+        ecs.add_component(id, Movable::new(v2!(), v2!(), v2!()));
+        let done = TweenEvent::new("done");
+        let mut tweens = Tweens::new()
+            .with(TweenTarget::PosX, Tween::new(0., 10., 1., Ease::Linear))
+            .with(TweenTarget::PosY, Tween::new(0., 20., 2., Ease::Linear))
+            .then()
+            .with(TweenTarget::PosX, Tween::new(10., 0., 1., Ease::Linear).with_event(done));
+
+        tweens.update_tweens(&mut ecs, id, 0.5);
+        assert_eq!(ecs.get_component::<Movable>(id).unwrap().pos, v2!(5., 5.));
+        tweens.update_tweens(&mut ecs, id, 1.);
+        // The second step waits for the slower tween of the first
+        assert_eq!(ecs.get_component::<Movable>(id).unwrap().pos, v2!(10., 15.));
+        tweens.update_tweens(&mut ecs, id, 0.5);
+        assert_eq!(ecs.get_component::<Movable>(id).unwrap().pos, v2!(10., 20.));
+        tweens.update_tweens(&mut ecs, id, 0.5);
+        assert_eq!(ecs.get_component::<Movable>(id).unwrap().pos, v2!(5., 20.));
+        assert!(!tweens.fired(done));
+        tweens.update_tweens(&mut ecs, id, 0.5);
+        assert!(tweens.fired(done));
+        assert!(tweens.finished());
+        tweens.update_tweens(&mut ecs, id, 0.5);
+        assert_eq!(tweens.events().count(), 0);
+    }
+    #[test]
+    fn every_entity_with_tweens_runs_and_reports_all_events(){
+        let mut ecs = ECS::new();
+        let plain = ecs.new_entity();
+        let id = ecs.new_entity();
+        ecs.add_component(plain, Movable::new(v2!(), v2!(), v2!()));
+        ecs.add_component(id, Movable::new(v2!(), v2!(), v2!()));
+        let (x_done, y_done) = (TweenEvent::new("x_done"), TweenEvent::new("y_done"));
+        ecs.add_component(id, Tweens::new()
+            .with(TweenTarget::PosX, Tween::new(0., 10., 1., Ease::Linear).with_event(x_done))
+            .with(TweenTarget::PosY, Tween::new(0., 20., 1., Ease::Linear).with_event(y_done)));
+
+        run_tweens(&mut ecs, 1.);
+        assert_eq!(ecs.get_component::<Movable>(id).unwrap().pos, v2!(10., 20.));
+        assert_eq!(ecs.get_component::<Movable>(plain).unwrap().pos, v2!());
+        let tweens = ecs.get_component::<Tweens>(id).unwrap();
+        assert_eq!(tweens.events().collect::<Vec<_>>(), [x_done, y_done]);
+    }
+    #[test]
+    fn tweens_past_the_cap_are_dropped(){
+        let mut ecs = ECS::new();
+        let id = ecs.new_entity();
+        ecs.add_component(id, Movable::new(v2!(), v2!(), v2!()));
+        let mut tweens = Tweens::new();
+        for _ in 0..MAX_TWEENS {
+            tweens = tweens.with(TweenTarget::PosX, Tween::new(0., 1., 1., Ease::Linear));
+        }
+        tweens = tweens.with(TweenTarget::PosY, Tween::new(0., 1., 1., Ease::Linear));
+        tweens.update_tweens(&mut ecs, id, 1.);
+        assert_eq!(ecs.get_component::<Movable>(id).unwrap().pos, v2!(1., 0.));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::prelude::*;
+use serde::Deserialize;
+
+pub const CONFIG_PATH: &str = "/config.toml";
+
+// Tunable gameplay values, loaded from `resources/config.toml`.
+// Missing fields fall back to the constants in `types_and_constants.rs`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub jump_velocity: f32,
+    pub dino_gravity: f32,
+    pub start_scroll_speed: f32,
+    pub max_scroll_speed: f32,
+    pub cactus_min_delay: f32,
+    pub ptero_speed: f32,
+    // Rocks and tumbleweeds move this much faster than the ground
+    pub rock_speed: f32,
+    pub tumbleweed_speed: f32,
+    // Upward velocity a tumbleweed gets each time it hits the ground, and the gravity pulling it back
+    pub tumbleweed_bounce: f32,
+    pub tumbleweed_gravity: f32,
+    // Most obstacle entities the pool may allocate
+    pub obstacle_pool_cap: usize,
+    // Particles alive at once; new ones are dropped past this
+    pub max_particles: usize,
+    pub pickups: PickupConfig,
+    pub day_night: DayNightConfig,
+    // 1 means the first hit ends the run. Applies from the next run.
+    pub lives: u32,
+    // Seconds the dino ignores collisions after losing a life
+    pub invulnerability_time: f32,
+    // How zoomed in the camera starts out; it zooms back out to the normal view by top speed
+    pub camera_zoom_out: f32,
+    // Strength of the screen shake on hits, 0 turns it off
+    pub screen_shake: f32,
+    // Scale the game only by whole numbers, leaving wider bars around it
+    pub integer_scaling: bool,
+    // Start in fullscreen. F11 toggles it in game.
+    pub fullscreen: bool,
+    // Reload images, sounds, animations and this file when they change on disk
+    pub hot_reload: bool,
+    // Directory in `resources/themes` to take images and sounds from; F6 cycles through them
+    pub theme: String,
+    pub show_colliders: bool,
+    pub show_debug_info: bool,
+    pub pause_enabled: bool,
+    pub difficulty: DifficultyPreset,
+    // Only used when `difficulty = "custom"`
+    pub custom_difficulty: Option<Difficulty>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            jump_velocity: JUMP_VELOCITY,
+            dino_gravity: DINO_GRAVITY,
+            start_scroll_speed: START_SCROLL_SPEED,
+            max_scroll_speed: MAX_SCROLL_SPEED,
+            cactus_min_delay: CACTUS_MIN_DELAY,
+            ptero_speed: PTERO_SPEED,
+            rock_speed: ROCK_SPEED,
+            tumbleweed_speed: TUMBLEWEED_SPEED,
+            tumbleweed_bounce: TUMBLEWEED_BOUNCE,
+            tumbleweed_gravity: TUMBLEWEED_GRAVITY,
+            obstacle_pool_cap: OBSTACLE_POOL_CAP,
+            max_particles: MAX_PARTICLES,
+            pickups: PickupConfig::default(),
+            day_night: DayNightConfig::default(),
+            lives: LIVES,
+            invulnerability_time: INVULNERABILITY_TIME,
+            camera_zoom_out: CAMERA_ZOOM_OUT,
+            screen_shake: SCREEN_SHAKE,
+            integer_scaling: true,
+            fullscreen: false,
+            hot_reload: false,
+            theme: DEFAULT_THEME.to_string(),
+            show_colliders: SHOW_COLLIDERS,
+            show_debug_info: false,
+            pause_enabled: PAUSE_ENABLED,
+            difficulty: DifficultyPreset::Normal,
+            custom_difficulty: None,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn from_toml(src: &str) -> Result<GameConfig, toml::de::Error> {
+        let config: GameConfig = toml::from_str(src)?;
+        Ok(config.validated())
+    }
+
+    // Falls back to the defaults if the file is missing or can't be parsed
+    pub fn load(ctx: &mut Context) -> GameConfig {
+        load_toml_or_default(ctx, CONFIG_PATH, GameConfig::from_toml)
+    }
+
+    // Clamps every value into a range the game can handle
+    pub fn validated(mut self) -> GameConfig {
+        self.jump_velocity      = self.jump_velocity.clamp(200., 5000.);
+        self.dino_gravity       = self.dino_gravity.clamp(-20000., -500.);
+        self.start_scroll_speed = self.start_scroll_speed.clamp(100., 5000.);
+        self.max_scroll_speed   = self.max_scroll_speed.clamp(self.start_scroll_speed, 10000.);
+        self.cactus_min_delay   = self.cactus_min_delay.clamp(0.2, 5.);
+        self.ptero_speed        = self.ptero_speed.clamp(0., 1000.);
+        self.rock_speed         = self.rock_speed.clamp(0., 1000.);
+        self.tumbleweed_speed   = self.tumbleweed_speed.clamp(0., 1000.);
+        self.tumbleweed_bounce  = self.tumbleweed_bounce.clamp(0., 2000.);
+        self.tumbleweed_gravity = self.tumbleweed_gravity.clamp(-10000., -100.);
+        self.obstacle_pool_cap  = self.obstacle_pool_cap.clamp(1, 512);
+        self.max_particles      = self.max_particles.min(4096);
+        self.pickups            = self.pickups.validated();
+        self.day_night          = self.day_night.validated();
+        self.lives              = self.lives.clamp(1, 9);
+        self.invulnerability_time = self.invulnerability_time.clamp(0., 10.);
+        self.camera_zoom_out    = self.camera_zoom_out.clamp(0., 1.);
+        self.screen_shake       = self.screen_shake.clamp(0., 2.);
+        self.custom_difficulty  = self.custom_difficulty.map(Difficulty::validated);
+        self
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        match (self.difficulty, &self.custom_difficulty) {
+            (DifficultyPreset::Custom, Some(custom)) => custom.clone(),
+            (preset, _) => Difficulty::preset(preset, self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn missing_fields_use_defaults(){
+        let config = GameConfig::from_toml("jump_velocity = 1000.0").unwrap();
+        assert_eq!(config.jump_velocity, 1000.0);
+        assert_eq!(config.dino_gravity, DINO_GRAVITY);
+        assert_eq!(config.show_colliders, SHOW_COLLIDERS);
+    }
+    #[test]
+    fn values_are_clamped(){
+        let config = GameConfig::from_toml("
+            dino_gravity = 100.0
+            start_scroll_speed = 900.0
+            max_scroll_speed = 500.0
+        ").unwrap();
+        assert_eq!(config.dino_gravity, -500.0);
+        assert_eq!(config.max_scroll_speed, 900.0);
+    }
+    #[test]
+    fn shipped_config_parses(){
+        let config = GameConfig::from_toml(include_str!("../resources/config.toml")).unwrap();
+        assert_eq!(config.custom_difficulty.unwrap().flight_patterns.len(), 3);
+    }
+    #[test]
+    fn custom_difficulty(){
+        let config = GameConfig::from_toml(r#"
+            difficulty = "custom"
+            [custom_difficulty]
+            progress = "score"
+            scroll_speed = { type = "linear", start = 500.0, rate = 1.0, max = 1000.0 }
+            spawn_gap_min = { type = "constant", value = 1.0 }
+            spawn_gap_range = { type = "constant", value = 0.5 }
+            ptero_probability = { type = "keyframed", keys = [[300.0, 0.5], [0.0, 0.0]] }
+            cluster_size = { type = "piecewise", points = [[0.0, 1.0], [1000.0, 3.0]] }
+            [[custom_difficulty.flight_patterns]]
+            pattern = { type = "swoop", depth = 40.0, x = 0.0, width = 250.0 }
+            weight = { type = "keyframed", keys = [[0.0, 0.0], [800.0, 1.0]] }
+        "#).unwrap();
+        let difficulty = config.difficulty();
+        assert_eq!(difficulty.scroll_speed(0., 200.), 700.0);
+        assert_eq!(difficulty.ptero_probability(0., 100.), 0.0);
+        assert_eq!(difficulty.ptero_probability(0., 300.), 0.5);
+        assert_eq!(difficulty.cluster_size(0., 500.), 2);
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        assert_eq!(difficulty.flight_pattern(0., 0., &mut rng), FlightPattern::Straight);
+        assert_eq!(difficulty.flight_pattern(0., 900., &mut rng), FlightPattern::Swoop { depth: 40., x: 0., width: 250. });
+    }
+}
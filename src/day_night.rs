@@ -0,0 +1,162 @@
+// Day/night cycle. Nights start at score milestones, like in the original game,
+// and the whole frame gets inverted while they last.
+use crate::prelude::*;
+use serde::Deserialize;
+use ggez::graphics::{BlendMode, Canvas, DrawParam, Drawable};
+
+const NUM_OF_STARS: usize = 3;
+// Screen pixels per second the moon and the stars drift to the left
+const MOON_SPEED: f32 = 12.;
+const STAR_SPEED: f32 = 18.;
+// How far off screen the moon and stars go before wrapping around
+const SKY_MARGIN: f32 = 60.;
+// Stars are placed between these heights, in screen coordinates
+const STAR_MIN_Y: f32 = 40.;
+const STAR_MAX_Y: f32 = 220.;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DayNightConfig {
+    // A night starts every time the score passes a multiple of this. 0 turns the cycle off.
+    pub night_every: f32,
+    // Seconds a night lasts, not counting the fades
+    pub night_length: f32,
+    // Seconds it takes to fade between day and night
+    pub transition_time: f32,
+}
+
+impl Default for DayNightConfig {
+    fn default() -> Self {
+        DayNightConfig {
+            night_every: 700.,
+            night_length: 12.,
+            transition_time: 1.,
+        }
+    }
+}
+
+impl DayNightConfig {
+    pub fn validated(mut self) -> DayNightConfig {
+        self.night_every     = self.night_every.max(0.);
+        self.night_length    = self.night_length.clamp(0., 600.);
+        self.transition_time = self.transition_time.clamp(0., 10.);
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DayNight {
+    // Milestones passed this run
+    milestone: u32,
+    night_left: f32,
+    // 0 is full day, 1 is full night
+    blend: f32,
+    moon_x: f32,
+    stars: [Vec2; NUM_OF_STARS],
+}
+
+impl DayNight {
+    pub fn new() -> DayNight {
+        DayNight {
+            milestone: 0,
+            night_left: 0.,
+            blend: 0.,
+            moon_x: SCREEN.0 * 0.75,
+            stars: [v2!(); NUM_OF_STARS],
+        }
+    }
+    pub fn blend(&self) -> f32 {
+        self.blend
+    }
+    pub fn update(&mut self, config: &DayNightConfig, rng: &mut Rand32, score: f32, dt: f32) {
+        if config.night_every > 0. {
+            let milestone = (score / config.night_every) as u32;
+            if milestone > self.milestone {
+                if self.blend <= 0. {self.place_stars(rng)}
+                self.milestone = milestone;
+                self.night_left = config.night_length;
+            }
+        }
+        self.night_left = (self.night_left - dt).max(0.);
+
+        let target = if self.night_left > 0. {1.} else {0.};
+        let step = if config.transition_time > 0. {dt / config.transition_time} else {1.};
+        self.blend =
+            if target > self.blend {(self.blend + step).min(target)}
+            else {(self.blend - step).max(target)};
+
+        if self.blend <= 0. {return}
+        self.moon_x -= MOON_SPEED * dt;
+        if self.moon_x < -SKY_MARGIN {self.moon_x = SCREEN.0 + SKY_MARGIN}
+        for star in self.stars.iter_mut() {
+            star.x -= STAR_SPEED * dt;
+            if star.x < -SKY_MARGIN {star.x = SCREEN.0 + SKY_MARGIN}
+        }
+    }
+    fn place_stars(&mut self, rng: &mut Rand32) {
+        let band = SCREEN.0 / NUM_OF_STARS as f32;
+        for (i, star) in self.stars.iter_mut().enumerate() {
+            star.x = band * (i as f32 + rng.rand_float());
+            star.y = STAR_MIN_Y + (STAR_MAX_Y - STAR_MIN_Y) * rng.rand_float();
+        }
+    }
+
+    // The moon and stars fade in with the night. They're drawn in the day palette
+    // and come out light once the frame is inverted.
+    pub fn draw_sky(&self, ctx: &mut Context, assets: &Assets) -> GameResult {
+        if self.blend <= 0. {return Ok(())}
+        let color = Color::new(1., 1., 1., self.blend);
+        let moon = assets.get_image(AssetTag::MOON);
+        graphics::draw(ctx, moon, DrawParam::new().dest(v2!(self.moon_x.floor(), 50.)).color(color))?;
+        let star = assets.get_image(AssetTag::STAR);
+        for pos in self.stars.iter() {
+            graphics::draw(ctx, star, DrawParam::new().dest(pos.floor()).color(color))?;
+        }
+        Ok(())
+    }
+
+    // Draws the frame rendered into `scene`, inverted as far as the night has come in
+    pub fn present(&self, ctx: &mut Context, scene: &Canvas, param: DrawParam) -> GameResult {
+        if self.blend > 0. {
+            graphics::draw(ctx, scene, param)?;
+            let mut invert = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(0., 0., SCREEN.0, SCREEN.1),
+                Color::WHITE,
+            )?;
+            invert.set_blend_mode(Some(BlendMode::Invert));
+            graphics::draw(ctx, &invert, param)?;
+        }
+        graphics::draw(ctx, scene, param.color(Color::new(1., 1., 1., 1. - self.blend)))
+    }
+}
+
+impl Default for DayNight {
+    fn default() -> Self {
+        DayNight::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn night_fades_in_at_milestones_and_out_after(){
+        let config = DayNightConfig::default();
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        let mut day_night = DayNight::new();
+        day_night.update(&config, &mut rng, 699., 0.5);
+        assert_eq!(day_night.blend(), 0.);
+        day_night.update(&config, &mut rng, 700., 0.5);
+        assert_eq!(day_night.blend(), 0.5);
+        day_night.update(&config, &mut rng, 710., 0.5);
+        assert_eq!(day_night.blend(), 1.);
+        day_night.update(&config, &mut rng, 800., 10.5);
+        assert_eq!(day_night.blend(), 1.);
+        day_night.update(&config, &mut rng, 810., 0.5);
+        assert_eq!(day_night.blend(), 0.5);
+        day_night.update(&config, &mut rng, 820., 0.5);
+        assert_eq!(day_night.blend(), 0.);
+    }
+}
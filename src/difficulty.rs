@@ -0,0 +1,296 @@
+use crate::prelude::*;
+use serde::Deserialize;
+
+// A value that changes as the run goes on. `x` is either the run time in seconds
+// or the score, depending on `Difficulty::progress`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DifficultyCurve {
+    Constant { value: f32 },
+    Linear { start: f32, rate: f32, max: f32 },
+    // Linear interpolation between (x, value) points
+    Piecewise { points: Vec<(f32, f32)> },
+    // Approaches `cap` from `start`, `rate` controls how fast
+    ExponentialToCap { start: f32, cap: f32, rate: f32 },
+    // Jumps to the value of the last key whose x has been reached
+    Keyframed { keys: Vec<(f32, f32)> },
+}
+
+impl DifficultyCurve {
+    pub fn evaluate(&self, x: f32) -> f32 {
+        match self {
+            DifficultyCurve::Constant { value } => *value,
+            DifficultyCurve::Linear { start, rate, max } => (start + rate * x).min(*max),
+            DifficultyCurve::Piecewise { points } => {
+                if points.is_empty() {return 0.}
+                if x <= points[0].0 {return points[0].1}
+                for w in points.windows(2) {
+                    let (x0, y0) = w[0];
+                    let (x1, y1) = w[1];
+                    if x <= x1 {
+                        if x1 <= x0 {return y1}
+                        return y0 + (y1 - y0) * (x - x0) / (x1 - x0);
+                    }
+                }
+                points[points.len() - 1].1
+            }
+            DifficultyCurve::ExponentialToCap { start, cap, rate } => {
+                cap - (cap - start) * (-rate * x).exp()
+            }
+            DifficultyCurve::Keyframed { keys } => {
+                let mut value = keys.first().map_or(0., |k| k.1);
+                for (key_x, key_value) in keys {
+                    if x < *key_x {break}
+                    value = *key_value;
+                }
+                value
+            }
+        }
+    }
+
+    fn sort_points(&mut self) {
+        match self {
+            DifficultyCurve::Piecewise { points: keys } |
+            DifficultyCurve::Keyframed { keys } => {
+                keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+            }
+            _ => (),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Progress {
+    Time,
+    Score,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifficultyPreset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Custom,
+}
+
+// A ptero flight pattern and how likely it is, relative to the others
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct WeightedFlightPattern {
+    pub pattern: FlightPattern,
+    pub weight: DifficultyCurve,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Difficulty {
+    pub progress: Progress,
+    pub scroll_speed: DifficultyCurve,
+    // Time between spawns is `spawn_gap_min + random * spawn_gap_range` seconds
+    pub spawn_gap_min: DifficultyCurve,
+    pub spawn_gap_range: DifficultyCurve,
+    pub ptero_probability: DifficultyCurve,
+    // Chances of the ground hazards, checked after the ptero's
+    #[serde(default = "never")]
+    pub rock_probability: DifficultyCurve,
+    #[serde(default = "never")]
+    pub tumbleweed_probability: DifficultyCurve,
+    pub cluster_size: DifficultyCurve,
+    // Chance that a spawn is an authored chunk, and the highest chunk tier allowed
+    #[serde(default = "never")]
+    pub chunk_probability: DifficultyCurve,
+    #[serde(default = "never")]
+    pub chunk_tier: DifficultyCurve,
+    // Pteros fly straight when this is empty
+    #[serde(default)]
+    pub flight_patterns: Vec<WeightedFlightPattern>,
+}
+
+fn never() -> DifficultyCurve {
+    DifficultyCurve::Constant { value: 0. }
+}
+
+impl Difficulty {
+    pub fn preset(preset: DifficultyPreset, config: &GameConfig) -> Difficulty {
+        let speed = |rate| DifficultyCurve::Linear {
+            start: config.start_scroll_speed,
+            rate,
+            max: config.max_scroll_speed,
+        };
+        let constant = |value| DifficultyCurve::Constant { value };
+        let keyframed = |keys: &[(f32, f32)]| DifficultyCurve::Keyframed { keys: keys.to_vec() };
+        let flight = |pattern, weight| WeightedFlightPattern { pattern, weight };
+        let bob = FlightPattern::SineBob { amplitude: 12., wavelength: 400. };
+        let swoop = FlightPattern::Swoop { depth: 40., x: 0., width: 250. };
+        let climb = FlightPattern::HeightChange { delta: 40., x: 200., width: 150. };
+        match preset {
+            DifficultyPreset::Easy => Difficulty {
+                progress: Progress::Time,
+                scroll_speed: speed(3.5),
+                spawn_gap_min: constant(config.cactus_min_delay + 0.3),
+                spawn_gap_range: constant(1.5),
+                ptero_probability: constant(0.08),
+                rock_probability: constant(0.),
+                tumbleweed_probability: constant(0.05),
+                cluster_size: constant(1.),
+                chunk_probability: constant(0.2),
+                chunk_tier: constant(1.),
+                flight_patterns: vec![],
+            },
+            DifficultyPreset::Normal | DifficultyPreset::Custom => Difficulty {
+                progress: Progress::Time,
+                scroll_speed: speed(5.5),
+                spawn_gap_min: constant(config.cactus_min_delay),
+                spawn_gap_range: constant(1.3),
+                ptero_probability: constant(1. / 7.),
+                rock_probability: keyframed(&[(0., 0.), (30., 0.08)]),
+                tumbleweed_probability: keyframed(&[(0., 0.05), (60., 0.1)]),
+                cluster_size: constant(1.),
+                chunk_probability: constant(0.3),
+                chunk_tier: keyframed(&[(0., 1.), (45., 2.), (90., 3.)]),
+                flight_patterns: vec![
+                    flight(FlightPattern::Straight, constant(1.)),
+                    flight(bob, keyframed(&[(0., 0.), (30., 0.3), (60., 0.6)])),
+                    flight(climb, keyframed(&[(0., 0.), (60., 0.3)])),
+                    flight(swoop, keyframed(&[(0., 0.), (90., 0.3)])),
+                ],
+            },
+            DifficultyPreset::Hard => Difficulty {
+                progress: Progress::Score,
+                scroll_speed: DifficultyCurve::ExponentialToCap {
+                    start: config.start_scroll_speed + 100.,
+                    cap: config.max_scroll_speed,
+                    rate: 0.0008,
+                },
+                spawn_gap_min: DifficultyCurve::Piecewise {
+                    points: vec![(0., config.cactus_min_delay), (1500., 0.5)],
+                },
+                spawn_gap_range: constant(1.0),
+                ptero_probability: keyframed(&[(0., 0.15), (500., 0.25), (1500., 0.35)]),
+                rock_probability: keyframed(&[(0., 0.08), (600., 0.12)]),
+                tumbleweed_probability: keyframed(&[(0., 0.08), (800., 0.12)]),
+                cluster_size: keyframed(&[(0., 1.), (1000., 2.)]),
+                chunk_probability: constant(0.5),
+                chunk_tier: keyframed(&[(0., 2.), (800., 3.)]),
+                flight_patterns: vec![
+                    flight(FlightPattern::Straight, constant(1.)),
+                    flight(bob, constant(0.5)),
+                    flight(climb, keyframed(&[(0., 0.2), (500., 0.5)])),
+                    flight(swoop, keyframed(&[(0., 0.), (800., 0.5)])),
+                ],
+            },
+        }
+    }
+
+    pub fn validated(mut self) -> Difficulty {
+        for curve in [
+            &mut self.scroll_speed,
+            &mut self.spawn_gap_min,
+            &mut self.spawn_gap_range,
+            &mut self.ptero_probability,
+            &mut self.rock_probability,
+            &mut self.tumbleweed_probability,
+            &mut self.cluster_size,
+            &mut self.chunk_probability,
+            &mut self.chunk_tier,
+        ] {
+            curve.sort_points();
+        }
+        for flight in self.flight_patterns.iter_mut() {
+            flight.weight.sort_points();
+        }
+        self
+    }
+
+    fn x(&self, time: f32, score: f32) -> f32 {
+        match self.progress {
+            Progress::Time => time,
+            Progress::Score => score,
+        }
+    }
+
+    pub fn scroll_speed(&self, time: f32, score: f32) -> f32 {
+        self.scroll_speed.evaluate(self.x(time, score)).max(0.)
+    }
+
+    pub fn spawn_gap(&self, time: f32, score: f32, rng: &mut Rand32) -> f32 {
+        let x = self.x(time, score);
+        let min = self.spawn_gap_min.evaluate(x).max(0.2);
+        let range = self.spawn_gap_range.evaluate(x).max(0.);
+        min + rng.rand_float() * range
+    }
+
+    pub fn ptero_probability(&self, time: f32, score: f32) -> f32 {
+        self.ptero_probability.evaluate(self.x(time, score)).clamp(0., 1.)
+    }
+
+    pub fn rock_probability(&self, time: f32, score: f32) -> f32 {
+        self.rock_probability.evaluate(self.x(time, score)).clamp(0., 1.)
+    }
+
+    pub fn tumbleweed_probability(&self, time: f32, score: f32) -> f32 {
+        self.tumbleweed_probability.evaluate(self.x(time, score)).clamp(0., 1.)
+    }
+
+    pub fn cluster_size(&self, time: f32, score: f32) -> usize {
+        self.cluster_size.evaluate(self.x(time, score)).max(1.) as usize
+    }
+
+    pub fn chunk_probability(&self, time: f32, score: f32) -> f32 {
+        self.chunk_probability.evaluate(self.x(time, score)).clamp(0., 1.)
+    }
+
+    pub fn chunk_tier(&self, time: f32, score: f32) -> u32 {
+        self.chunk_tier.evaluate(self.x(time, score)).max(0.) as u32
+    }
+
+    pub fn flight_pattern(&self, time: f32, score: f32, rng: &mut Rand32) -> FlightPattern {
+        let x = self.x(time, score);
+        let weights: Vec<f32> = self.flight_patterns.iter()
+            .map(|flight| flight.weight.evaluate(x).max(0.))
+            .collect();
+        let total: f32 = weights.iter().sum();
+        if total <= 0. {return FlightPattern::Straight}
+        let mut roll = rng.rand_float() * total;
+        for (flight, weight) in self.flight_patterns.iter().zip(weights) {
+            if roll < weight {return flight.pattern}
+            roll -= weight;
+        }
+        FlightPattern::Straight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn piecewise_interpolates(){
+        let curve = DifficultyCurve::Piecewise { points: vec![(0., 10.), (10., 20.), (20., 0.)] };
+        assert_eq!(curve.evaluate(-5.), 10.);
+        assert_eq!(curve.evaluate(5.), 15.);
+        assert_eq!(curve.evaluate(15.), 10.);
+        assert_eq!(curve.evaluate(100.), 0.);
+    }
+    #[test]
+    fn keyframed_steps(){
+        let curve = DifficultyCurve::Keyframed { keys: vec![(0., 1.), (100., 2.), (200., 3.)] };
+        assert_eq!(curve.evaluate(99.), 1.);
+        assert_eq!(curve.evaluate(100.), 2.);
+        assert_eq!(curve.evaluate(1000.), 3.);
+    }
+    #[test]
+    fn exponential_approaches_cap(){
+        let curve = DifficultyCurve::ExponentialToCap { start: 100., cap: 200., rate: 1. };
+        assert_eq!(curve.evaluate(0.), 100.);
+        assert!(curve.evaluate(50.) > 199.9);
+        assert!(curve.evaluate(50.) <= 200.);
+    }
+    #[test]
+    fn normal_matches_old_scroll_speed(){
+        let difficulty = Difficulty::preset(DifficultyPreset::Normal, &GameConfig::default());
+        assert_eq!(difficulty.scroll_speed(0., 0.), START_SCROLL_SPEED);
+        assert_eq!(difficulty.scroll_speed(10., 0.), START_SCROLL_SPEED + 55.);
+        assert_eq!(difficulty.scroll_speed(1000., 0.), MAX_SCROLL_SPEED);
+    }
+}
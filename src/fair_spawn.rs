@@ -0,0 +1,369 @@
+// Reachability check for obstacle layouts.
+// Replays the dino's jump physics tick by tick against the upcoming obstacles
+// and tells whether some sequence of jumps gets past all of them.
+use crate::prelude::*;
+use collision::BoundType;
+
+// Upper bound on simulated ticks, in case an obstacle never leaves the screen
+const MAX_TICKS: usize = 1200;
+// Extra room around obstacles when checking new spawns. Absorbs the small speed-ups
+// applied to obstacles that are already on screen.
+pub const FAIR_SPAWN_MARGIN: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObstacleSnapshot {
+    pub pos: Vec2,
+    pub velocity: f32,
+    pub collider: Collider,
+    // Pteros change height as they fly
+    pub flight: Option<Ptero>,
+    // Tumbleweeds fall and bounce
+    pub bounce: Option<Bounce>,
+    pub vertical_velocity: f32,
+    pub gravity: f32,
+}
+
+impl ObstacleSnapshot {
+    pub fn from_entity(ecs: &ECS, entity_id: usize) -> ObstacleSnapshot {
+        let mov = ecs.get_component::<Movable>(entity_id).unwrap();
+        ObstacleSnapshot {
+            pos: mov.pos,
+            velocity: mov.velocity.x,
+            collider: ecs.get_component::<Collider>(entity_id).unwrap(),
+            flight: ecs.get_component::<Ptero>(entity_id),
+            bounce: ecs.get_component::<Bounce>(entity_id),
+            vertical_velocity: mov.velocity.y,
+            gravity: mov.gravity.y,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    Ground,
+    // Ticks since a jump started from the ground
+    Jump(usize),
+    // Ticks along the arc the dino was already on when the search started
+    Current(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JumpModel {
+    entity_id: usize,
+    jump_velocity: f32,
+    gravity: f32,
+}
+
+impl JumpModel {
+    pub fn new(entity_id: usize, config: &GameConfig) -> JumpModel {
+        JumpModel {
+            entity_id,
+            jump_velocity: config.jump_velocity,
+            gravity: config.dino_gravity,
+        }
+    }
+
+    pub fn apply_config(&mut self, config: &GameConfig) {
+        self.jump_velocity = config.jump_velocity;
+        self.gravity = config.dino_gravity;
+    }
+
+    // Heights of the dino for every tick it stays in the air, same math as `Movable::update_pos`
+    fn arc(&self, mut y: f32, mut velocity: f32, lowest_point_offs: f32, dt: f32) -> Vec<f32> {
+        let mut arc = Vec::new();
+        loop {
+            velocity += self.gravity * dt;
+            y += velocity * dt;
+            if y + lowest_point_offs < GROUND_Y_COORD || arc.len() >= MAX_TICKS {
+                return arc;
+            }
+            arc.push(y);
+        }
+    }
+
+    // Meant to be called during an update, after the input has been handled,
+    // so the dino can't jump until the next one.
+    pub fn can_clear(&self, ecs: &ECS, obstacles: &[ObstacleSnapshot], margin: f32) -> bool {
+        self.search(ecs, obstacles, margin, false).is_some()
+    }
+
+    // Returns the ticks (counting from the next update) on which the dino has to jump
+    // to get past every obstacle, or None if that's impossible.
+    pub fn plan(&self, ecs: &ECS, obstacles: &[ObstacleSnapshot], margin: f32) -> Option<Vec<usize>> {
+        self.search(ecs, obstacles, margin, true)
+    }
+
+    fn search(&self, ecs: &ECS, obstacles: &[ObstacleSnapshot], margin: f32, can_jump_now: bool) -> Option<Vec<usize>> {
+        let dt = 1.0 / DESIRED_FPS as f32;
+        let mov = ecs.get_component::<Movable>(self.entity_id).unwrap();
+        let col = ecs.get_component::<Collider>(self.entity_id).unwrap();
+        let lowest_point_offs = col.get_bound_offset(BoundType::Down).y;
+        let ground_y = GROUND_Y_COORD - lowest_point_offs;
+        let dino_left = mov.pos.x + col.get_bound_offset(BoundType::Left).x;
+
+        let jump_arc = self.arc(ground_y, self.jump_velocity, lowest_point_offs, dt);
+        let current_arc =
+            if mov.on_ground {Vec::new()}
+            else {self.arc(mov.pos.y, mov.velocity.y, lowest_point_offs, dt)};
+
+        // Only the obstacles the dino hasn't reached yet matter
+        let obstacles: Vec<ObstacleSnapshot> = obstacles.iter()
+            .filter(|o| o.pos.x + o.collider.get_bound_offset(BoundType::Right).x >= dino_left)
+            .copied()
+            .collect();
+        let hitboxes: Vec<Collider> = obstacles.iter().map(|o| o.collider.grown(margin)).collect();
+
+        let index = |phase: Phase| match phase {
+            Phase::Ground     => 0,
+            Phase::Jump(k)    => 1 + k,
+            Phase::Current(k) => 1 + jump_arc.len() + k,
+        };
+        let height = |phase: Phase| match phase {
+            Phase::Ground     => ground_y,
+            Phase::Jump(k)    => jump_arc[k],
+            Phase::Current(k) => current_arc[k],
+        };
+        // (phase, jumped this tick) pairs reachable from a phase
+        let next = |phase: Phase| -> Vec<(Phase, bool)> {
+            let advance = |k: usize, arc_len: usize, make: fn(usize) -> Phase|
+                if k + 1 < arc_len {make(k + 1)} else {Phase::Ground};
+            match phase {
+                Phase::Ground => vec![
+                    (Phase::Ground, false),
+                    (if jump_arc.is_empty() {Phase::Ground} else {Phase::Jump(0)}, true),
+                ],
+                Phase::Jump(k)    => vec![(advance(k, jump_arc.len(), Phase::Jump), false)],
+                Phase::Current(k) => vec![(advance(k, current_arc.len(), Phase::Current), false)],
+            }
+        };
+
+        let state_count = 1 + jump_arc.len() + current_arc.len();
+        // Moves available on the first update. Jumping needs the dino to already be on the ground.
+        let mut candidates: Vec<(usize, Phase, bool)> =
+            if mov.on_ground && can_jump_now {next(Phase::Ground).into_iter().map(|(p, j)| (0, p, j)).collect()}
+            else if mov.on_ground || current_arc.is_empty() {vec![(0, Phase::Ground, false)]}
+            else {vec![(0, Phase::Current(0), false)]};
+
+        // history[tick][state] = (previous state, jumped)
+        let mut history: Vec<Vec<Option<(usize, bool)>>> = Vec::new();
+        let mut frontier: Vec<Phase> = Vec::new();
+        let mut positions: Vec<Vec2> = obstacles.iter().map(|o| o.pos).collect();
+        let mut vertical_velocities: Vec<f32> = obstacles.iter().map(|o| o.vertical_velocity).collect();
+        loop {
+            for ((pos, vy), o) in positions.iter_mut().zip(vertical_velocities.iter_mut()).zip(obstacles.iter()) {
+                pos.x += o.velocity * dt;
+                if let Some(flight) = o.flight {
+                    pos.y = flight.height_at(pos.x);
+                }
+                if let Some(bounce) = o.bounce {
+                    // Same order as `Movable::update_pos` followed by `Bounce::bounce_off_ground`
+                    *vy += o.gravity * dt;
+                    pos.y += *vy * dt;
+                    (pos.y, *vy) = bounce.land(pos.y, *vy, o.collider.get_bound_offset(BoundType::Down).y);
+                }
+            }
+            let mut layer: Vec<Option<(usize, bool)>> = vec![None; state_count];
+            frontier.clear();
+            for (prev, phase, jumped) in candidates.drain(..) {
+                let ind = index(phase);
+                if layer[ind].is_some() {continue}
+                let dino_pos = v2!(mov.pos.x, height(phase));
+                let hit = hitboxes.iter().zip(positions.iter())
+                    .any(|(hitbox, pos)| col.check_collision(dino_pos, hitbox, *pos));
+                if hit {continue}
+                layer[ind] = Some((prev, jumped));
+                frontier.push(phase);
+            }
+            history.push(layer);
+            if frontier.is_empty() {return None}
+
+            let all_passed = hitboxes.iter().zip(positions.iter())
+                .all(|(hitbox, pos)| pos.x + hitbox.get_bound_offset(BoundType::Right).x < dino_left);
+            if all_passed || history.len() >= MAX_TICKS {break}
+
+            for phase in frontier.iter() {
+                for (next_phase, jumped) in next(*phase) {
+                    candidates.push((index(*phase), next_phase, jumped));
+                }
+            }
+        }
+
+        // Walk back from any surviving state to collect the jump ticks
+        let mut jumps = Vec::new();
+        let mut state = index(frontier[0]);
+        for (tick, layer) in history.iter().enumerate().rev() {
+            let (prev, jumped) = layer[state].unwrap();
+            if jumped {jumps.push(tick)}
+            state = prev;
+        }
+        jumps.reverse();
+        Some(jumps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CACTUS_SIZES: [(f32, f32); 6] = [
+        (34., 70.), (68., 70.), (102., 70.),
+        (50., 100.), (100., 100.), (150., 100.),
+    ];
+    const PTERO_WIDTH: f32 = 92.;
+    const ROCK_SIZE: (f32, f32) = (36., 28.);
+    const TUMBLEWEED_WIDTH: f32 = 44.;
+
+    struct World {
+        ecs: ECS,
+        dino: usize,
+        manager: ObstacleManager,
+        model: JumpModel,
+    }
+
+    // Same setup as `MainState`, minus everything that needs a graphics context
+    fn world(config: &GameConfig) -> World {
+        let mut ecs = ECS::new();
+        let mut manager = ObstacleManager::new(config, vec![]);
+        for (tag, (w, h)) in AssetTag::cactus_tags().into_iter().zip(CACTUS_SIZES) {
+            let cactus = ecs.new_entity();
+            ecs.add_component(cactus, Movable::new(
+                v2!(SCREEN.0 + 50.0, cactus_y(w, h)),
+                v2!(-config.start_scroll_speed, 0.0),
+                Vec2::ZERO,
+            ));
+            ecs.add_component(cactus, cactus_collider(w, h));
+            manager.add_cactus(cactus, tag);
+        }
+        for _ in 0..2 {
+            let ptero = ecs.new_entity();
+            ecs.add_component(ptero, Movable::new(v2!(SCREEN.0 + 50., GROUND_Y_COORD + 40.), v2!(-30., 0.), v2!()));
+            ecs.add_component(ptero, ptero_collider(PTERO_WIDTH));
+            ecs.add_component(ptero, Ptero::new());
+            manager.add_ptero(ptero, AssetTag::PTERO_ANIM);
+        }
+        let rock = ecs.new_entity();
+        ecs.add_component(rock, Movable::new(v2!(SCREEN.0 + 50., rock_y(ROCK_SIZE.1)), v2!(), v2!()));
+        ecs.add_component(rock, rock_collider(ROCK_SIZE.0, ROCK_SIZE.1));
+        manager.add_rock(rock, AssetTag::ROCK_ANIM);
+        let tumbleweed = ecs.new_entity();
+        ecs.add_component(tumbleweed, Movable::new(v2!(SCREEN.0 + 50., GROUND_Y_COORD), v2!(), v2!()));
+        ecs.add_component(tumbleweed, tumbleweed_collider(TUMBLEWEED_WIDTH));
+        ecs.add_component(tumbleweed, Bounce::new(config.tumbleweed_bounce));
+        manager.add_tumbleweed(tumbleweed, AssetTag::TUMBLEWEED_ANIM);
+        let dino = ecs.new_entity();
+        let mut dino_movable = Movable::new(
+            v2!(-400.0, GROUND_Y_COORD + 43.),
+            v2!(0.0, 0.0),
+            v2!(0.0, config.dino_gravity),
+        );
+        dino_movable.ground_check_on();
+        ecs.add_component(dino, dino_movable);
+        ecs.add_component(dino, DinoController::collider());
+        let model = JumpModel::new(dino, config);
+        manager.set_jump_model(model);
+        manager.set_chunks(ChunkLibrary::from_toml(include_str!("../resources/chunks.toml")).unwrap());
+        World { ecs, dino, manager, model }
+    }
+
+    // Plays `seconds` of the game with a bot that follows the planner.
+    // Returns the time of death, if any.
+    fn play(config: &GameConfig, seed: u64, seconds: f32) -> Option<f32> {
+        let mut w = world(config);
+        let mut rng = Rand32::new(seed);
+        let dt = 1.0 / DESIRED_FPS as f32;
+        let mut score = 0.;
+        // Jump ticks left in the current plan, replanned whenever the obstacles change
+        let mut plan: Vec<usize> = Vec::new();
+        let mut planned_for: Option<Vec<(f32, f32)>> = None;
+        for tick in 0..(seconds / dt) as usize {
+            let time = tick as f32 * dt;
+            let obstacles = w.manager.snapshots(&w.ecs);
+            let signature: Vec<(f32, f32)> = obstacles.iter()
+                .map(|o| (o.flight.map_or(o.pos.y, |f| f.base_y()), o.velocity))
+                .collect();
+            if planned_for.as_ref() != Some(&signature) {
+                // Leave some room for speed-ups while in the air, if possible
+                plan = w.model.plan(&w.ecs, &obstacles, FAIR_SPAWN_MARGIN / 2.)
+                    .or_else(|| w.model.plan(&w.ecs, &obstacles, 0.))
+                    .unwrap_or_default()
+                    .iter().map(|t| t + tick).collect();
+                planned_for = Some(signature);
+            }
+            if plan.first() == Some(&tick) {
+                plan.remove(0);
+                let mut mov = w.ecs.get_component::<Movable>(w.dino).unwrap();
+                mov.jump(config.jump_velocity);
+                w.ecs.set_component(w.dino, mov);
+            }
+
+            score += dt * (10. + score / 300.);
+            w.manager.update(&mut w.ecs, &mut rng, time, dt, score);
+            Movable::update_pos(&mut w.ecs, w.dino, dt);
+            if w.manager.check_collision(&w.ecs, w.dino) {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn single_cactus_is_clearable(){
+        let config = GameConfig::default();
+        let mut w = world(&config);
+        let id = w.manager.id(5);
+        let mut mov = w.ecs.get_component::<Movable>(id).unwrap();
+        mov.pos.x = 0.;
+        w.ecs.set_component(id, mov);
+        assert!(w.model.can_clear(&w.ecs, &[ObstacleSnapshot::from_entity(&w.ecs, id)], 0.));
+    }
+
+    #[test]
+    fn touching_big_cacti_are_not_clearable(){
+        let config = GameConfig::default();
+        let mut w = world(&config);
+        let mut obstacles = Vec::new();
+        for (i, x) in [(5, 0.), (4, 130.), (3, 210.)] {
+            let id = w.manager.id(i);
+            let mut mov = w.ecs.get_component::<Movable>(id).unwrap();
+            mov.pos.x = x;
+            w.ecs.set_component(id, mov);
+            obstacles.push(ObstacleSnapshot::from_entity(&w.ecs, id));
+        }
+        assert!(!w.model.can_clear(&w.ecs, &obstacles, 0.));
+    }
+
+    #[test]
+    fn fuzz_seeds_have_no_impossible_layouts(){
+        let brutal = GameConfig {
+            difficulty: DifficultyPreset::Custom,
+            custom_difficulty: Some(Difficulty {
+                progress: Progress::Time,
+                scroll_speed: DifficultyCurve::Linear { start: 900., rate: 10., max: 2100. },
+                spawn_gap_min: DifficultyCurve::Constant { value: 0.4 },
+                spawn_gap_range: DifficultyCurve::Constant { value: 0.3 },
+                ptero_probability: DifficultyCurve::Constant { value: 0.3 },
+                rock_probability: DifficultyCurve::Constant { value: 0.15 },
+                tumbleweed_probability: DifficultyCurve::Constant { value: 0.15 },
+                cluster_size: DifficultyCurve::Constant { value: 2. },
+                chunk_probability: DifficultyCurve::Constant { value: 0.3 },
+                chunk_tier: DifficultyCurve::Constant { value: 3. },
+                flight_patterns: [
+                    FlightPattern::Straight,
+                    FlightPattern::SineBob { amplitude: 20., wavelength: 300. },
+                    FlightPattern::Swoop { depth: 60., x: -100., width: 300. },
+                    FlightPattern::HeightChange { delta: -40., x: 300., width: 100. },
+                ].into_iter()
+                    .map(|pattern| WeightedFlightPattern { pattern, weight: DifficultyCurve::Constant { value: 1. } })
+                    .collect(),
+            }),
+            ..GameConfig::default()
+        };
+        let hard = GameConfig { difficulty: DifficultyPreset::Hard, ..GameConfig::default() };
+        for config in [GameConfig::default(), hard, brutal] {
+            for seed in 0..6 {
+                let death = play(&config, RNG_DEFAULT_SEED + seed, 40.);
+                assert_eq!(death, None, "seed {seed} with {:?}", config.difficulty);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Notices files under `resources/` changing on disk while the game runs.
+// It checks modification times every so often, which is plenty for a folder this size.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Seconds between checks
+const POLL_INTERVAL: f32 = 0.5;
+
+pub struct FileWatcher {
+    root: PathBuf,
+    // Modification time of every file, by its path in the game's filesystem ("/images/cloud.png")
+    times: HashMap<String, SystemTime>,
+    timer: f32,
+}
+
+impl FileWatcher {
+    pub fn new(root: PathBuf) -> FileWatcher {
+        let mut watcher = FileWatcher {
+            root,
+            times: HashMap::new(),
+            timer: 0.,
+        };
+        watcher.times = watcher.scan();
+        watcher
+    }
+
+    fn scan(&self) -> HashMap<String, SystemTime> {
+        let mut times = HashMap::new();
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                if let (Some(name), Ok(time)) = (self.game_path(&path), entry.metadata().and_then(|m| m.modified())) {
+                    times.insert(name, time);
+                }
+            }
+        }
+        times
+    }
+    fn game_path(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<String> = relative.components().map(|part| part.as_os_str().to_string_lossy().into_owned()).collect();
+        Some(format!("/{}", parts.join("/")))
+    }
+
+    // Files added or modified since the last check, sorted
+    pub fn update(&mut self, dt: f32) -> Vec<String> {
+        self.timer += dt;
+        if self.timer < POLL_INTERVAL {return Vec::new()}
+        self.timer = 0.;
+        let times = self.scan();
+        let mut changed: Vec<String> = times.iter()
+            .filter(|(name, time)| self.times.get(*name) != Some(*time))
+            .map(|(name, _)| name.clone())
+            .collect();
+        changed.sort();
+        self.times = times;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn reports_changed_files(){
+        let root = std::env::temp_dir().join(format!("dino_watch_{}", std::process::id()));
+        fs::create_dir_all(root.join("images")).unwrap();
+        fs::write(root.join("config.toml"), "lives = 1").unwrap();
+        fs::write(root.join("images/cloud.png"), [0u8]).unwrap();
+        let mut watcher = FileWatcher::new(root.clone());
+        assert!(watcher.update(POLL_INTERVAL).is_empty());
+
+        // Some filesystems only keep whole seconds
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        fs::File::options().write(true).open(root.join("images/cloud.png")).unwrap().set_modified(later).unwrap();
+        fs::write(root.join("images/star.png"), [0u8]).unwrap();
+        assert!(watcher.update(POLL_INTERVAL / 2.).is_empty());
+        assert_eq!(watcher.update(POLL_INTERVAL / 2.), ["/images/cloud.png", "/images/star.png"]);
+        assert!(watcher.update(POLL_INTERVAL).is_empty());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -51,7 +51,8 @@ impl InputState{
 pub fn player_handle_input(ctx: &Context, ecs: &mut ECS, assets: &mut Assets, entity_id: usize, input: &mut InputState, _dt: f32) {
     if input.game_active{
         if input.jump() {
-            ecs.get_component::<DinoController>(entity_id).unwrap().jump(ctx, ecs, assets);
+            let mut controller = ecs.get_component::<DinoController>(entity_id).unwrap();
+            controller.jump(ctx, ecs, assets);
             input.jump_end();
         }
     }
@@ -6,6 +6,19 @@ pub mod input;
 pub mod assets;
 pub mod ecs;
 pub mod ui;
+pub mod config;
+pub mod difficulty;
+pub mod fair_spawn;
+pub mod chunks;
+pub mod pickups;
+pub mod day_night;
+pub mod parallax;
+pub mod particles;
+pub mod camera;
+pub mod viewport;
+pub mod hot_reload;
+pub mod atlas;
+pub mod sheet;
 
 pub mod prelude{
     pub use glam::*;
@@ -30,6 +43,19 @@ pub mod prelude{
         input::*,
         types_and_constants::*,
         ui::*,
+        config::*,
+        difficulty::*,
+        fair_spawn::*,
+        chunks::*,
+        pickups::*,
+        day_night::*,
+        parallax::*,
+        particles::*,
+        camera::*,
+        viewport::*,
+        hot_reload::*,
+        atlas::*,
+        sheet::*,
     };
 }
 
@@ -68,7 +94,32 @@ pub fn get_time() -> u64{
     since_the_epoch.as_secs()
 }
 
+// Uniform in `range.0..range.1`
+pub fn random_in(range: (f32, f32), rng: &mut Rand32) -> f32 {
+    range.0 + (range.1 - range.0) * rng.rand_float()
+}
+
 // File stuff
+// Missing files give the default quietly, unreadable or broken ones are reported first
+pub fn load_toml_or_default<T: Default, E: std::fmt::Display>(ctx: &mut Context, file_path: &str, parse: impl Fn(&str) -> Result<T, E>) -> T {
+    if !filesystem::is_file(ctx, file_path){
+        return T::default();
+    }
+    let mut buffer = String::new();
+    let read = filesystem::open(ctx, file_path).and_then(|mut file| Ok(file.read_to_string(&mut buffer)?));
+    if let Err(e) = read {
+        eprintln!("Failed to read {file_path}: {e}");
+        return T::default();
+    }
+    match parse(&buffer) {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("Failed to parse {file_path}: {e}");
+            T::default()
+        }
+    }
+}
+
 pub fn read_high_score_data(ctx: &mut Context) -> u32 {
     let file_path = path::Path::new("/high_score.txt");
     if !filesystem::is_file(ctx, file_path){
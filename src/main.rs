@@ -2,6 +2,7 @@
 
 use dino_game::prelude::*;
 
+use std::path::PathBuf;
 use std::time::Duration;
 use ggez::conf::Conf;
 use ggez::event::MouseButton;
@@ -10,8 +11,10 @@ struct EntityIds{
     dino:       usize,
     ground1:    usize,
     ground2:    usize,
-    cloud:      usize,
     ptero:      usize,
+    rock:       usize,
+    tumbleweed: usize,
+    pickups:    Vec<usize>,
 }
 
 struct Score{
@@ -21,6 +24,7 @@ struct Score{
 }
 
 struct MainState {
+    config: GameConfig,
     ecs: ECS,
     ent: EntityIds,
     obstacle_manager: ObstacleManager,
@@ -28,167 +32,272 @@ struct MainState {
     assets: Box<Assets>,
     rng: Rand32,
     restart_button: UIButton,
+    // Drops the restart button in from above the screen on game over
+    restart_slide: Tween,
     pub score: Score,
+    power_ups: PowerUps,
+    parallax: Parallax,
+    particles: ParticleSystem,
+    camera: Camera,
+    day_night: DayNight,
+    // The frame is drawn here first at the virtual resolution, then scaled into the window.
+    // The night also inverts it on the way.
+    scene: graphics::Canvas,
+    viewport: Viewport,
+    fullscreen: bool,
+    // Draws the next frame even while paused or idle, after the window changed size
+    redraw: bool,
+    // Where `resources/` is on disk, and a watcher on it while hot reloading is on
+    resource_dir: PathBuf,
+    watcher: Option<FileWatcher>,
+    // Game time, which slow motion slows down
+    clock: f32,
     lose_time: f32,
 }
 
 impl MainState {
-    fn new(ctx: &mut Context) -> GameResult<MainState> {
-        let assets = Assets::new(ctx);
+    fn new(ctx: &mut Context, resource_dir: PathBuf, audio: bool) -> GameResult<MainState> {
+        let config = GameConfig::load(ctx);
+        let assets = match Assets::with_theme(ctx, &config.theme, audio) {
+            Ok(assets) => assets,
+            Err(e) => {
+                eprintln!("{e}");
+                Assets::new(ctx, audio)?
+            }
+        };
 
         let mut ecs = ECS::new();
 
         let ground1 = ecs.new_entity();
         let ground2 = ecs.new_entity();
 
-        let cloud = ecs.new_entity();
-
         let mov_vec = vec![ground1, ground2];
 
         let cactus_tags = AssetTag::cactus_tags();
-        let mut obstacle_manager = ObstacleManager::with_capacity(cactus_tags.len(), CACTUS_MIN_DELAY, mov_vec);
-        for _ in 0..cactus_tags.len() {
+        let mut obstacle_manager = ObstacleManager::with_capacity(cactus_tags.len(), &config, mov_vec);
+        for tag in cactus_tags.iter() {
             let cactus = ecs.new_entity();
-            obstacle_manager.add_cactus(cactus);
+            obstacle_manager.add_cactus(cactus, *tag);
         }
+        obstacle_manager.set_chunks(ChunkLibrary::load(ctx));
 
         let ptero = ecs.new_entity();
-        obstacle_manager.add_ptero(ptero);
+        obstacle_manager.add_ptero(ptero, AssetTag::PTERO_ANIM);
+
+        let rock = ecs.new_entity();
+        obstacle_manager.add_rock(rock, AssetTag::ROCK_ANIM);
+        let tumbleweed = ecs.new_entity();
+        obstacle_manager.add_tumbleweed(tumbleweed, AssetTag::TUMBLEWEED_ANIM);
+
+        let mut pickups = Vec::with_capacity(PickupKind::ALL.len());
+        for kind in PickupKind::ALL {
+            let pickup = ecs.new_entity();
+            obstacle_manager.add_pickup(pickup, kind.asset_tag());
+            pickups.push(pickup);
+        }
 
         let dino = ecs.new_entity();
 
         let high_score = read_high_score_data(ctx);
 
-        let mut restart_button = UIButton::new(&assets, AssetTag::RestartButton, v2!());
+        let mut restart_button = UIButton::new(&assets, AssetTag::RESTART_BUTTON, v2!());
         restart_button.deactivate();
 
-        let rng = Rand32::new(get_time());
+        let mut rng = Rand32::new(get_time());
+        let parallax = Parallax::new(&mut ecs, &assets, &mut rng, BackgroundDef::load(ctx));
+        let particles = ParticleSystem::new(config.max_particles);
+        let scene = Viewport::canvas(ctx)?;
+        let viewport = Viewport::new(graphics::drawable_size(ctx), config.integer_scaling);
+        let fullscreen = config.fullscreen;
 
-        let s = MainState{
+        let mut s = MainState{
+            config,
             ecs,
             ent: EntityIds{
                 dino,
                 ground1,
                 ground2,
-                cloud,
                 ptero,
+                rock,
+                tumbleweed,
+                pickups,
             },
             obstacle_manager,
             input: InputState::new(),
             assets,
             rng,
             restart_button,
+            restart_slide: Tween::done(0.),
             score: Score{
                 cur: 0.,
                 high: high_score,
                 next_sound: 100.0
             },
+            power_ups: PowerUps::new(),
+            parallax,
+            particles,
+            camera: Camera::new(),
+            day_night: DayNight::new(),
+            scene,
+            viewport,
+            fullscreen: false,
+            redraw: true,
+            resource_dir,
+            watcher: None,
+            clock: 0.,
             lose_time: 0.,
         };
+        if fullscreen {s.toggle_fullscreen(ctx)}
+        s.watch_resources();
         Ok(s)
     }
-    fn start(&mut self, _ctx: &mut Context) {
+    fn start(&mut self, _ctx: &mut Context) -> GameResult {
         // DINO
         let mut dino_movable = Movable::new(
             v2!(-400.0, GROUND_Y_COORD + 43.),
             v2!(0.0, 0.0),
-            v2!(0.0, DINO_GRAVITY),
+            v2!(0.0, self.config.dino_gravity),
         );
         dino_movable.ground_check_on();
-        let dino_collider_body = BoxCollider::new(v2!(14., 25.)).with_offset(v2!(-6., -18.));
-        let dino_collider_head = BoxCollider::new(v2!(22., 17.)).with_offset(v2!(18., 32.));
-        let dino_collider = Collider::new_double(dino_collider_body, dino_collider_head);
-        let dino_anim = Animation::new(&mut self.assets, AssetTag::DinoAnimRun);
-        let dino_state_machine = AnimStateMachine::new(&mut self.assets, AssetTag::DinoStateMachine, DinoState::Run);
+        let dino_collider = DinoController::collider();
+        let dino_state_machine = AnimStateMachine::new(&self.assets, AssetTag::DINO_STATE_MACHINE)?;
+        let dino_anim = dino_state_machine.start_anim(&self.assets)?;
 
         self.ecs.add_component(self.ent.dino, dino_movable);
         self.ecs.add_component(self.ent.dino, dino_collider);
         self.ecs.add_component(self.ent.dino, dino_anim);
-        self.ecs.add_component(self.ent.dino, DinoController::new(self.ent.dino, AssetTag::JumpSound));
-        self.ecs.add_component(self.ent.dino, DinoState::Run);
+        self.ecs.add_component(self.ent.dino, DinoController::new(self.ent.dino, AssetTag::JUMP_SOUND, self.config.jump_velocity));
+        self.ecs.add_component(self.ent.dino, AnimParams::default());
         self.ecs.add_component(self.ent.dino, dino_state_machine);
+        self.ecs.add_component(self.ent.dino, Health::new(self.config.lives, self.config.invulnerability_time));
+        self.ecs.add_component(self.ent.dino, RenderLayer::DINO);
+        self.ecs.add_component(
+            self.ent.dino,
+            ParticleEmitter::new(v2!(-20., -43.))
+                .with_trail(ParticleEffect::DUST, 20.)
+                .with_step(ParticleEffect::STEP)
+                .with_jump(ParticleEffect::JUMP)
+                .with_land(ParticleEffect::LANDING),
+        );
+        self.obstacle_manager.set_jump_model(JumpModel::new(self.ent.dino, &self.config));
         // self.components.add_component(dino, CircleGraphic::new(47.0));
 
+        // Obstacle colliders and heights come from their images, see fit_to_images()
+
         // PTERO
-        let img = self.assets.get_image(AssetTag::Ptero1).unwrap();
-        let ptero_wid = img.width() as f32;
-        let ptero_col = Collider::new_single(BoxCollider::new(v2!(ptero_wid/2. - 8., 20.)).with_offset(v2!(8., 4.)));
-        let ptero_scr = EndlessScroll::new(ptero_wid);
         let ptero_mov = Movable::new(v2!(SCREEN.0 + 50., GROUND_Y_COORD + 40.), v2!(-30.,0.), v2!());
-        let ptero_anim = Animation::new(&self.assets, AssetTag::PteroAnim);
+        let ptero_anim = Animation::new(&self.assets, AssetTag::PTERO_ANIM)?;
 
         self.ecs.add_component(self.ent.ptero, ptero_mov);
-        self.ecs.add_component(self.ent.ptero, ptero_col);
         self.ecs.add_component(self.ent.ptero, ptero_anim);
-        self.ecs.add_component(self.ent.ptero, ptero_scr);
+        self.ecs.add_component(self.ent.ptero, EndlessScroll::new(0.));
         self.ecs.add_component(self.ent.ptero, Ptero::new());
+        self.ecs.add_component(self.ent.ptero, RenderLayer::OBSTACLES);
+
+        // ROCK
+        self.ecs.add_component(self.ent.rock, Movable::new(v2!(SCREEN.0 + 50., 0.), v2!(), v2!()));
+        self.ecs.add_component(self.ent.rock, Animation::new(&self.assets, AssetTag::ROCK_ANIM)?);
+        self.ecs.add_component(self.ent.rock, RenderLayer::OBSTACLES);
+
+        // TUMBLEWEED
+        self.ecs.add_component(self.ent.tumbleweed, Movable::new(v2!(SCREEN.0 + 50., GROUND_Y_COORD), v2!(), v2!()));
+        self.ecs.add_component(self.ent.tumbleweed, Animation::new(&self.assets, AssetTag::TUMBLEWEED_ANIM)?);
+        self.ecs.add_component(self.ent.tumbleweed, Bounce::new(self.config.tumbleweed_bounce));
+        self.ecs.add_component(self.ent.tumbleweed, RenderLayer::OBSTACLES);
+
+        // PICKUPS
+        for (pickup, kind) in self.ent.pickups.iter().zip(PickupKind::ALL) {
+            self.ecs.add_component(*pickup, Movable::new(v2!(SCREEN.0 + 50., 0.), v2!(), v2!()));
+            self.ecs.add_component(*pickup, Sprite::new(kind.asset_tag()));
+            self.ecs.add_component(*pickup, Pickup::new(kind));
+            self.ecs.add_component(*pickup, RenderLayer::PICKUPS);
+        }
 
         // CACTUS
         let cactus_tags = AssetTag::cactus_tags();
         for i in 0..cactus_tags.len() {
             let cactus = self.obstacle_manager.id(i);
-            let img = self.assets.get_image(cactus_tags[i]).unwrap();
-            // Some math for calculating cactus colliders
-            let mut hs = v2!(img.width() as f32 / 2.0, img.height() as f32 / 2.0);
-            let q: f32 = 0.7;
-            let col_offs = v2!(0., -hs.y * (1. - q) / 2.);
-            hs.y *= q;
-            let col_low = BoxCollider::new(hs).with_offset(col_offs);
-            let pad = 18.0 * img.height() as f32 / 100.0;
-            let mut hs = v2!(img.width() as f32 / 2.0 - pad, img.height() as f32 / 2.0);
-            hs.y -= 2.;
-            let col_high = BoxCollider::new(hs);
-            let offset_y =
-                if img.height() == 100{ // big cactus
-                    if img.width() > 100 {-2.}
-                    else {-4.}
-                } else {0.};
             self.ecs.add_component(
                 cactus,
                 Movable::new(
-                    v2!(SCREEN.0 + 50.0, GROUND_Y_COORD + img.height() as f32 / 2.0 + offset_y),
-                    v2!(-START_SCROLL_SPEED, 0.0),
+                    v2!(SCREEN.0 + 50.0, 0.),
+                    v2!(-self.config.start_scroll_speed, 0.0),
                     Vec2::ZERO,
                 )
             );
-            self.ecs.add_component(cactus,Collider::new_double(col_low, col_high));
             self.ecs.add_component(cactus, Sprite::new(cactus_tags[i]));
+            self.ecs.add_component(cactus, RenderLayer::OBSTACLES);
             // self.components.add_component(cactus, CircleGraphic::new(20.0));
         }
 
         // GROUND
         let mut ground_mov = Movable::new(
             v2!(0., 0.),
-            v2!(-START_SCROLL_SPEED, 0.),
+            v2!(-self.config.start_scroll_speed, 0.),
             v2!()
         );
-        let ground_spr_1 = Sprite::new(AssetTag::Ground1);
-        let ground_spr_2 = Sprite::new(AssetTag::Ground2);
-        let w = self.assets.get_image(AssetTag::Ground1).unwrap().width() as f32;
+        let ground_spr_1 = Sprite::new(AssetTag::GROUND_1);
+        let ground_spr_2 = Sprite::new(AssetTag::GROUND_2);
+        let w = self.assets.get_image(AssetTag::GROUND_1).width() as f32;
         let ground_scr = EndlessScroll::new(w);
 
         self.ecs.add_component(self.ent.ground1, ground_mov);
         self.ecs.add_component(self.ent.ground1, ground_spr_1);
         self.ecs.add_component(self.ent.ground1, ground_scr);
+        self.ecs.add_component(self.ent.ground1, RenderLayer::GROUND);
 
         ground_mov.pos.x += w;
         self.ecs.add_component(self.ent.ground2, ground_mov);
         self.ecs.add_component(self.ent.ground2, ground_spr_2);
         self.ecs.add_component(self.ent.ground2, ground_scr);
+        self.ecs.add_component(self.ent.ground2, RenderLayer::GROUND);
 
-        // CLOUD
-        let cloud_mov = Movable::new(
-            v2!(0., 200.),
-            v2!(-START_SCROLL_SPEED / 2.0, 0.),
-            v2!(0., 0.)
-        );
-        let cloud_spr = Sprite::new(AssetTag::Cloud);
-        let w = self.assets.get_image(AssetTag::Cloud).unwrap().width() as f32;
-        let cloud_scr = EndlessScroll::new(w);
-
-        self.ecs.add_component(self.ent.cloud, cloud_mov);
-        self.ecs.add_component(self.ent.cloud, cloud_spr);
-        self.ecs.add_component(self.ent.cloud, cloud_scr);
+        self.fit_to_images();
+        Ok(())
+    }
+    // Sizes everything that depends on the images: obstacle and button colliders, obstacle heights, and the ground tiles
+    fn fit_to_images(&mut self) {
+        self.obstacle_manager.fit_to_images(&mut self.ecs, &self.assets);
+        self.restart_button.fit_to_image(&self.assets);
+
+        let w = self.assets.get_image(AssetTag::GROUND_1).width() as f32;
+        let ground1 = self.ecs.get_component::<Movable>(self.ent.ground1).unwrap();
+        let mut ground2 = self.ecs.get_component::<Movable>(self.ent.ground2).unwrap();
+        ground2.pos.x = ground1.pos.x + w;
+        self.ecs.set_component(self.ent.ground2, ground2);
+        self.ecs.set_component(self.ent.ground1, EndlessScroll::new(w));
+        self.ecs.set_component(self.ent.ground2, EndlessScroll::new(w));
+
+        // Dust comes from the "feet" slice of the dino's sheet
+        if let (Some(offset), Some(mut emitter)) = (
+            self.assets.slice_offset(AssetTag::DINO_ANIM_RUN, "feet"),
+            self.ecs.get_component::<ParticleEmitter>(self.ent.dino),
+        ) {
+            emitter.set_offset(offset);
+            self.ecs.set_component(self.ent.dino, emitter);
+        }
+    }
+    // Loads the theme named in the config, keeping the current one if that fails
+    fn apply_theme(&mut self, ctx: &mut Context) {
+        if self.assets.theme() == self.config.theme {return}
+        match Assets::with_theme(ctx, &self.config.theme, self.assets.audio()) {
+            Ok(assets) => {
+                self.assets = assets;
+                self.fit_to_images();
+            }
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+    // Switches to the next theme in `resources/themes`, after the default one
+    fn next_theme(&mut self, ctx: &mut Context) {
+        let themes = themes(ctx);
+        let next = match themes.iter().position(|theme| *theme == self.config.theme) {
+            Some(i) if i + 1 < themes.len() => themes[i + 1].clone(),
+            Some(_) => DEFAULT_THEME.to_string(),
+            None => themes.first().cloned().unwrap_or_else(|| DEFAULT_THEME.to_string()),
+        };
+        self.config.theme = next;
+        self.apply_theme(ctx);
     }
     fn restart(&mut self, ctx: &mut Context) {
         self.input = InputState::new();
@@ -199,12 +308,19 @@ impl MainState {
         }
 
         self.score.cur = 0.;
+        self.power_ups = PowerUps::new();
+        self.particles.clear();
+        self.camera = Camera::new();
+        self.day_night = DayNight::new();
 
         // DINO
         let mut dino_movable = self.ecs.get_component::<Movable>(self.ent.dino).unwrap();
         dino_movable.pos.y = GROUND_Y_COORD + 43.;
         self.ecs.set_component(self.ent.dino, dino_movable);
-        self.ecs.set_component(self.ent.dino, DinoState::Run);
+        let mut params = self.ecs.get_component::<AnimParams>(self.ent.dino).unwrap();
+        params.set_bool(AnimParam::DEAD, false);
+        self.ecs.set_component(self.ent.dino, params);
+        self.ecs.set_component(self.ent.dino, Health::new(self.config.lives, self.config.invulnerability_time));
 
         // CACTUS
         for id in self.obstacle_manager.ids() {
@@ -216,14 +332,61 @@ impl MainState {
 
         self.restart_button.deactivate();
     }
+    fn reload_config(&mut self, ctx: &mut Context) {
+        self.config = GameConfig::load(ctx);
+        self.obstacle_manager.apply_config(&self.config);
+        self.particles.set_cap(self.config.max_particles);
+
+        let mut dino_movable = self.ecs.get_component::<Movable>(self.ent.dino).unwrap();
+        dino_movable.gravity = v2!(0.0, self.config.dino_gravity);
+        self.ecs.set_component(self.ent.dino, dino_movable);
+
+        let mut dino_controller = self.ecs.get_component::<DinoController>(self.ent.dino).unwrap();
+        dino_controller.set_jump_velocity(self.config.jump_velocity);
+        self.ecs.set_component(self.ent.dino, dino_controller);
+
+        self.viewport.set_integer_scaling(self.config.integer_scaling);
+        self.watch_resources();
+        self.apply_theme(ctx);
+    }
+    fn watch_resources(&mut self) {
+        if !self.config.hot_reload {
+            self.watcher = None;
+        } else if self.watcher.is_none() {
+            self.watcher = Some(FileWatcher::new(self.resource_dir.clone()));
+        }
+    }
+    fn hot_reload(&mut self, ctx: &mut Context) {
+        let changed = match self.watcher.as_mut() {
+            Some(watcher) => watcher.update(timer::delta(ctx).as_secs_f32()),
+            None => return,
+        };
+        if changed.is_empty() {return}
+        if changed.iter().any(|file| file == CONFIG_PATH) {
+            self.reload_config(ctx);
+        }
+        match self.assets.reload(ctx, &changed) {
+            Ok(()) => self.fit_to_images(),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+    fn toggle_fullscreen(&mut self, ctx: &mut Context) {
+        self.fullscreen = !self.fullscreen;
+        let mode = if self.fullscreen {conf::FullscreenType::Desktop} else {conf::FullscreenType::Windowed};
+        if let Err(e) = graphics::set_fullscreen(ctx, mode) {
+            eprintln!("Failed to switch fullscreen: {e}");
+        }
+        self.viewport.resize(graphics::drawable_size(ctx));
+        self.redraw = true;
+    }
 }
 
 
 impl event::EventHandler<ggez::GameError> for MainState {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.hot_reload(ctx);
         while timer::check_update_time(ctx, DESIRED_FPS) {
             let dt = 1.0 / (DESIRED_FPS as f32);
-            let time = timer::time_since_start(ctx).as_secs_f32();
 
             // INPUT STUFF
             input::player_handle_input(ctx, &mut self.ecs, &mut self.assets, self.ent.dino, &mut self.input, dt);
@@ -232,36 +395,123 @@ impl event::EventHandler<ggez::GameError> for MainState {
                 self.restart(ctx);
                 return Ok(());
             }
-            if self.input.pause() || !self.input.game_active() {continue}
+            if self.input.pause() {continue}
+            if !self.input.game_active() {
+                // Let the death effect play out
+                self.particles.simulate(0., dt);
+                self.camera.update(&mut self.rng, dt);
+                self.restart_slide.update(dt);
+                self.restart_button.pos.y = self.restart_slide.value();
+                continue
+            }
+
+            // Slow motion scales the game clock, power-up timers keep running in real time
+            self.power_ups.update(dt);
+            let dt = dt * self.power_ups.time_scale(&self.config.pickups);
+            self.clock += dt;
+            let time = self.clock;
 
             // EVERYTHING ELSE
             self.score.cur += dt * (10. + self.score.cur / 300.);
             if self.score.cur >= self.score.next_sound {
-                let _ = self.assets.get_audio_mut(AssetTag::PointSound).unwrap().play(ctx);
+                let _ = self.assets.get_audio_mut(AssetTag::POINT_SOUND).play(ctx);
                 self.score.next_sound += 100.;
             }
-
-            self.obstacle_manager.update(&mut self.ecs, &mut self.rng, time, dt);
+            self.day_night.update(&self.config.day_night, &mut self.rng, self.score.cur, dt);
+
+            self.obstacle_manager.update(&mut self.ecs, &mut self.rng, time, dt, self.score.cur);
+            self.parallax.update(&mut self.ecs, &mut self.rng, self.obstacle_manager.scroll_speed(), dt);
+            let speed_range = self.config.max_scroll_speed - self.config.start_scroll_speed;
+            let progress = (self.obstacle_manager.scroll_speed() - self.config.start_scroll_speed) / speed_range.max(1.);
+            self.camera.zoom_out(self.config.camera_zoom_out, progress);
+            self.camera.update(&mut self.rng, dt);
+            let scroll_factor = self.obstacle_manager.scroll_speed() / self.config.start_scroll_speed;
+            if let Some(anims) = self.ecs.borrow_component_vec_mut::<Animation>() {
+                for anim in anims.iter_mut().flatten() {
+                    anim.set_scroll_factor(scroll_factor);
+                }
+            }
 
             update! {
                 [&mut self.ecs, &self.assets, &mut self.rng, time, dt]
                 DinoController:                     self.ent.dino;
-                EndlessScroll:                      self.ent.ground1, self.ent.ground2, self.ent.cloud;
-                Movable:                            self.ent.dino, self.ent.ground1, self.ent.ground2, self.ent.cloud;
-                Ptero:                              self.ent.ptero;
-                AnimStateMachine::<DinoState>:      self.ent.dino;
-                Animation:                          self.ent.dino, self.ent.ptero;
+                EndlessScroll:                      self.ent.ground1, self.ent.ground2;
+                Movable:                            self.ent.dino, self.ent.ground1, self.ent.ground2;
+                AnimStateMachine:                   self.ent.dino;
+                Animation:                          self.ent.dino;
+                Health:                             self.ent.dino;
             };
+            if self.ecs.get_component::<Animation>(self.ent.dino).and_then(|anim| anim.event()) == Some(AnimEvent::FOOTSTEP) {
+                let _ = self.assets.get_audio_mut(AssetTag::FOOTSTEP_SOUND).play(ctx);
+            }
+            self.particles.update(&mut self.ecs, &mut self.rng, self.obstacle_manager.scroll_speed(), dt);
+            // The pool may have grown more obstacles than the ones created in start()
+            for id in self.obstacle_manager.ids() {
+                if self.ecs.get_component::<Animation>(id).is_some() {
+                    update! {
+                        [&mut self.ecs, &self.assets, &mut self.rng, time, dt]
+                        Animation:                  id;
+                    };
+                }
+                if self.ecs.get_component::<Pickup>(id).is_some() {
+                    update! {
+                        [&mut self.ecs, &self.assets, &mut self.rng, time, dt]
+                        Pickup:                     id;
+                    };
+                }
+            }
+            // After the updates above, so tweens win over what they set
+            run_tweens(&mut self.ecs, dt);
+            self.obstacle_manager.hide_collected(&mut self.ecs);
+
+            // PICKUPS
+            if self.power_ups.active(PickupKind::Magnet) {
+                let dino_pos = self.ecs.get_component::<Movable>(self.ent.dino).unwrap().pos;
+                let pickups = &self.config.pickups;
+                self.obstacle_manager.attract_coins(&mut self.ecs, dino_pos, pickups.magnet_radius, pickups.magnet_speed, dt);
+            }
+            for kind in self.obstacle_manager.collect_pickups(&mut self.ecs, self.ent.dino) {
+                self.score.cur += self.power_ups.collect(kind, &self.config.pickups);
+                let _ = self.assets.get_audio_mut(kind.sound_tag()).play(ctx);
+            }
+            let mut dino_controller = self.ecs.get_component::<DinoController>(self.ent.dino).unwrap();
+            dino_controller.set_air_jumps(self.power_ups.active(PickupKind::DoubleJump) as u32);
+            self.ecs.set_component(self.ent.dino, dino_controller);
+
+            let mut health = self.ecs.get_component::<Health>(self.ent.dino).unwrap();
+            let mut hit =
+                if health.invulnerable() {None}
+                else {self.obstacle_manager.colliding_obstacle(&self.ecs, self.ent.dino)};
+            if let Some(id) = hit {
+                if self.power_ups.absorb_hit() {
+                    self.obstacle_manager.remove(&mut self.ecs, id);
+                    let _ = self.assets.get_audio_mut(AssetTag::SHIELD_BREAK_SOUND).play(ctx);
+                    self.camera.add_trauma(0.3 * self.config.screen_shake);
+                    hit = None;
+                }
+                else if health.take_hit() {
+                    self.obstacle_manager.remove(&mut self.ecs, id);
+                    let _ = self.assets.get_audio_mut(AssetTag::HURT_SOUND).play(ctx);
+                    self.camera.add_trauma(0.5 * self.config.screen_shake);
+                    self.ecs.set_component(self.ent.dino, health);
+                    hit = None;
+                }
+            }
 
             // Losing the game
-            if self.obstacle_manager.check_collision(&mut self.ecs, self.ent.dino) {
+            if hit.is_some() {
                 // println!("\nGame over!");
-                let _ = self.assets.get_audio_mut(AssetTag::DeathSound).unwrap().play(ctx);
-
-                self.ecs.set_component::<DinoState>(self.ent.dino, DinoState::Dead);
+                let _ = self.assets.get_audio_mut(AssetTag::DEATH_SOUND).play(ctx);
+                let dino_pos = self.ecs.get_component::<Movable>(self.ent.dino).unwrap().pos;
+                self.particles.burst(&ParticleEffect::DEATH, dino_pos, &mut self.rng);
+                self.camera.add_trauma(0.8 * self.config.screen_shake);
+
+                let mut params = self.ecs.get_component::<AnimParams>(self.ent.dino).unwrap();
+                params.set_bool(AnimParam::DEAD, true);
+                self.ecs.set_component(self.ent.dino, params);
                 update! {
                     [&mut self.ecs, &self.assets, &mut self.rng, time, dt]
-                    AnimStateMachine::<DinoState>:      self.ent.dino;
+                    AnimStateMachine:                   self.ent.dino;
                     Animation:                          self.ent.dino;
                 };
 
@@ -273,7 +523,9 @@ impl event::EventHandler<ggez::GameError> for MainState {
                 }
 
                 self.restart_button.activate();
-                self.lose_time = time;
+                self.restart_slide = Tween::new(SCREEN.1 / 2. + 40., 0., 0.8, Ease::BounceOut);
+                self.restart_button.pos.y = self.restart_slide.value();
+                self.lose_time = timer::time_since_start(ctx).as_secs_f32();
 
                 self.draw(ctx)?;
                 self.input.game_over();
@@ -284,32 +536,36 @@ impl event::EventHandler<ggez::GameError> for MainState {
     }
     
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        if self.input.pause() || !self.input.game_active() {
+        let idle = !self.input.game_active() && self.particles.is_empty() && !self.camera.shaking() && self.restart_slide.finished();
+        if !self.redraw && (self.input.pause() || idle) {
             timer::sleep(Duration::new(0, 1_000_000_000 / DESIRED_FPS));
             return Ok(());
         }
+        self.redraw = false;
 
+        graphics::set_canvas(ctx, Some(&self.scene));
+        Camera::reset(ctx, SCREEN)?;
         const RGB_VAL: f32 = 247. / 255.;
         graphics::clear(ctx, Color::new(RGB_VAL, RGB_VAL, RGB_VAL, 1.0));
+        self.day_night.draw_sky(ctx, &self.assets)?;
 
         let screen_size = SCREEN;
 
-        for (sprite, movable) in iter_zip!(self.ecs, Sprite, Movable) {
-            sprite.draw(ctx, &self.ecs, &mut self.assets, 0, movable.pos, screen_size)?;
-        }
-
-        for (anim, movable) in iter_zip!(self.ecs, Animation, Movable) {
-            anim.draw(ctx, &self.ecs, &mut self.assets, 0, movable.pos, screen_size)?;
-        }
+        self.camera.apply(ctx, screen_size)?;
+        let mut draw_calls = draw_entities(ctx, &self.ecs, &self.assets, screen_size, ..RenderLayer::PARTICLES)?;
+        self.particles.draw(ctx, screen_size)?;
+        draw_calls += draw_entities(ctx, &self.ecs, &self.assets, screen_size, RenderLayer::PARTICLES..)?;
 
+        // UI goes on top of every render layer
         self.restart_button.draw(ctx, &self.ecs, &self.assets, 0, v2!(), SCREEN)?;
 
         // Draw colliders:
-        if SHOW_COLLIDERS {
+        if self.config.show_colliders {
             for (col, movable) in iter_zip!(self.ecs, Collider, Movable) {
                 col.draw(ctx, &self.ecs, &mut self.assets, 0, movable.pos, screen_size)?;
             }
         }
+        Camera::reset(ctx, screen_size)?;
 
         // Draw debug circles:
         // for (circle_graphic, movable) in iter_zip!(self.components, CircleGraphic, Movable) {
@@ -329,6 +585,21 @@ impl event::EventHandler<ggez::GameError> for MainState {
             )
         )?;
 
+        draw_power_ups(ctx, &self.assets, &self.power_ups, &self.config.pickups)?;
+        draw_lives(ctx, &self.assets, &self.ecs.get_component::<Health>(self.ent.dino).unwrap())?;
+
+        if self.config.show_debug_info {
+            let stats = self.obstacle_manager.pool_stats();
+            let debug_str = format!(
+                "pool {}/{} peak {} grown {} skipped {} sprite draws {}",
+                stats.active, stats.size, stats.high_water, stats.grown, stats.skipped, draw_calls
+            );
+            let debug_display = graphics::Text::new((debug_str, self.assets.font, 12.0));
+            graphics::draw(ctx, &debug_display, (v2!(15., SCREEN.1 - 27.), 0.0, Color::new(COL, COL, COL, 1.0)))?;
+        }
+
+        self.viewport.begin_window(ctx)?;
+        self.day_night.present(ctx, &self.scene, self.viewport.draw_param())?;
         graphics::present(ctx)?;
 
         timer::yield_now();
@@ -336,14 +607,20 @@ impl event::EventHandler<ggez::GameError> for MainState {
     }
 
     fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
-        let world_pos = screen_to_world_coords(SCREEN, v2!(x,y));
+        let screen_pos = self.viewport.window_to_virtual(v2!(x,y));
+        let world_pos = self.camera.screen_to_world(SCREEN, screen_pos);
         if button == MouseButton::Left {
-            if self.restart_button.col.contains_point(self.restart_button.pos, world_pos) {
+            if self.restart_button.contains_point(world_pos) {
                 self.restart(ctx);
             }
         }
     }
 
+    fn resize_event(&mut self, ctx: &mut Context, _width: f32, _height: f32) {
+        self.viewport.resize(graphics::drawable_size(ctx));
+        self.redraw = true;
+    }
+
     fn key_down_event(
         &mut self,
         _ctx: &mut Context,
@@ -359,13 +636,22 @@ impl event::EventHandler<ggez::GameError> for MainState {
         }
     }
 
-    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods) {
+    fn key_up_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymod: KeyMods) {
         match keycode {
             KeyCode::Space | KeyCode::Up => {
                 self.input.jump_end();
             }
+            KeyCode::F5 => {
+                self.reload_config(ctx);
+            }
+            KeyCode::F6 => {
+                self.next_theme(ctx);
+            }
+            KeyCode::F11 => {
+                self.toggle_fullscreen(ctx);
+            }
             KeyCode::Q => {
-                if PAUSE_ENABLED{
+                if self.config.pause_enabled{
                     self.input.toggle_pause();
                 }
             }
@@ -386,15 +672,26 @@ pub fn main() -> GameResult {
 
     let (w,h) = SCREEN;
 
-    let cb = ggez::ContextBuilder::new("dino game", "Kapanion")
+    let cb = || ggez::ContextBuilder::new("dino game", "Kapanion")
         .default_conf(Conf::new())
         .window_setup(conf::WindowSetup::default().icon("/images/dino_idle.png").title("Dino Game"))
-        .window_mode(conf::WindowMode::default().dimensions(w, h))
-        .add_resource_path(resource_dir);
-
-    let (mut ctx, event_loop) = cb.build()?;
+        .window_mode(conf::WindowMode::default().dimensions(w, h).min_dimensions(w / 4., h / 4.).resizable(true))
+        .add_resource_path(resource_dir.clone());
+
+    // Without an audio device the game still runs, just silently
+    let (mut ctx, event_loop, audio) = match cb().build() {
+        Ok((ctx, event_loop)) => (ctx, event_loop, true),
+        Err(ggez::GameError::AudioError(e)) => {
+            eprintln!("No audio: {e}");
+            let (ctx, event_loop) = cb().modules(conf::ModuleConf { audio: false, ..Default::default() }).build()?;
+            (ctx, event_loop, false)
+        }
+        Err(e) => return Err(e),
+    };
+    // Keeps the pixel art sharp when the camera zooms
+    graphics::set_default_filter(&mut ctx, graphics::FilterMode::Nearest);
 
-    let mut state = MainState::new(&mut ctx)?;
-    state.start(&mut ctx);
+    let mut state = MainState::new(&mut ctx, resource_dir, audio)?;
+    state.start(&mut ctx)?;
     event::run(ctx, event_loop, state)
 }
\ No newline at end of file
@@ -1,17 +1,30 @@
 use crate::prelude::*;
 use collision::BoundType;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ObstacleKind {
+    Cactus,
+    Ptero,
+    Rock,
+    Tumbleweed,
+    Pickup,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 struct ObstacleEntry {
     id: usize,
+    kind: ObstacleKind,
+    tag: AssetTag,
     active: bool,
     additional_speed: f32,
 }
 
 impl ObstacleEntry {
-    fn new(id: usize) -> ObstacleEntry {
+    fn new(id: usize, kind: ObstacleKind, tag: AssetTag) -> ObstacleEntry {
         ObstacleEntry {
             id,
+            kind,
+            tag,
             active: false,
             additional_speed: 0.,
         }
@@ -21,38 +34,90 @@ impl ObstacleEntry {
     }
 }
 
+// Components copied onto the entities the pool allocates once it runs out
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ObstaclePrefab {
+    movable: Movable,
+    collider: Collider,
+    sprite: Option<Sprite>,
+    animation: Option<Animation>,
+    ptero: Option<Ptero>,
+    bounce: Option<Bounce>,
+    pickup: Option<Pickup>,
+    layer: Option<RenderLayer>,
+}
+
+impl ObstaclePrefab {
+    fn from_entity(ecs: &ECS, id: usize) -> Option<ObstaclePrefab> {
+        Some(ObstaclePrefab {
+            movable: ecs.get_component(id)?,
+            collider: ecs.get_component(id)?,
+            sprite: ecs.get_component(id),
+            animation: ecs.get_component(id),
+            ptero: ecs.get_component(id),
+            bounce: ecs.get_component(id),
+            pickup: ecs.get_component(id),
+            layer: ecs.get_component(id),
+        })
+    }
+
+    fn instantiate(&self, ecs: &mut ECS) -> usize {
+        let id = ecs.new_entity();
+        ecs.add_component(id, self.movable);
+        ecs.add_component(id, self.collider);
+        if let Some(sprite) = self.sprite {ecs.add_component(id, sprite)}
+        if let Some(animation) = self.animation {ecs.add_component(id, animation)}
+        if let Some(ptero) = self.ptero {ecs.add_component(id, ptero)}
+        if let Some(bounce) = self.bounce {ecs.add_component(id, bounce)}
+        if let Some(pickup) = self.pickup {ecs.add_component(id, pickup)}
+        if let Some(layer) = self.layer {ecs.add_component(id, layer)}
+        id
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    // Entities owned by the pool, and how many of them are in play
+    pub size: usize,
+    pub active: usize,
+    // Most obstacles that were ever active at once
+    pub high_water: usize,
+    // Entities allocated after startup
+    pub grown: usize,
+    // Obstacles left out of a spawn because the pool was at its cap
+    pub skipped: usize,
+}
+
 struct ObstaclePool {
     obstacles: Vec<ObstacleEntry>,
+    cap: usize,
+    stats: PoolStats,
 }
 
 impl ObstaclePool {
-    fn new() -> ObstaclePool {
-        ObstaclePool {
-            obstacles: Vec::new(),
-        }
+    fn new(cap: usize) -> ObstaclePool {
+        ObstaclePool::with_capacity(0, cap)
     }
 
-    fn with_capacity(capacity: usize) -> ObstaclePool {
+    fn with_capacity(capacity: usize, cap: usize) -> ObstaclePool {
         ObstaclePool {
             obstacles: Vec::with_capacity(capacity),
+            cap,
+            stats: PoolStats::default(),
         }
     }
 
-    fn add_cactus(&mut self, id: usize){
-        self.obstacles.push(ObstacleEntry::new(id));
-    }
-
-    fn add_ptero(&mut self, id: usize){
-        let mut entry = ObstacleEntry::new(id);
-        entry.set_additional_speed(PTERO_SPEED);
+    fn add(&mut self, id: usize, kind: ObstacleKind, tag: AssetTag, speed: f32){
+        let mut entry = ObstacleEntry::new(id, kind, tag);
+        entry.set_additional_speed(speed);
         self.obstacles.push(entry);
     }
 
-    fn activate_next(&mut self, rng: &mut Rand32) -> Option<usize>{
+    fn activate_next(&mut self, rng: &mut Rand32, kind: ObstacleKind) -> Option<usize>{
         let next = (rng.rand_u32() as usize) % self.obstacles.len();
         for i in 0..self.obstacles.len(){
             let ind = (next + i) % self.obstacles.len();
-            if !self.obstacles[ind].active {
+            if !self.obstacles[ind].active && self.obstacles[ind].kind == kind {
                 self.obstacles[ind].active = true;
                 return Some(self.obstacles[ind].id);
             }
@@ -60,6 +125,69 @@ impl ObstaclePool {
         None
     }
 
+    // Reuses an inactive obstacle of the given kind, or allocates a new one
+    fn activate(&mut self, ecs: &mut ECS, rng: &mut Rand32, kind: ObstacleKind) -> Option<usize>{
+        let templates: Vec<usize> = (0..self.obstacles.len())
+            .filter(|i| self.obstacles[*i].kind == kind)
+            .collect();
+        if templates.is_empty() {return None}
+        let id = self.activate_next(rng, kind)
+            .or_else(|| self.grow(ecs, templates[(rng.rand_u32() as usize) % templates.len()]));
+        self.update_high_water();
+        id
+    }
+
+    // Prefers an obstacle with the given tag, but any of the same kind will do
+    fn activate_tag(&mut self, ecs: &mut ECS, tag: AssetTag, kind: ObstacleKind) -> Option<usize>{
+        let id = match self.obstacles.iter().position(|e| !e.active && e.tag == tag) {
+            Some(ind) => {
+                self.obstacles[ind].active = true;
+                Some(self.obstacles[ind].id)
+            }
+            None => {
+                let template = self.obstacles.iter().position(|e| e.tag == tag);
+                let grown = template.and_then(|ind| self.grow(ecs, ind));
+                grown.or_else(|| {
+                    let ind = self.obstacles.iter().position(|e| !e.active && e.kind == kind)?;
+                    self.obstacles[ind].active = true;
+                    Some(self.obstacles[ind].id)
+                })
+            }
+        };
+        self.update_high_water();
+        id
+    }
+
+    // Allocates a new active entity copying the components of the entry at `template`
+    fn grow(&mut self, ecs: &mut ECS, template: usize) -> Option<usize>{
+        if self.obstacles.len() >= self.cap {return None}
+        let template = self.obstacles[template];
+        let id = ObstaclePrefab::from_entity(ecs, template.id)?.instantiate(ecs);
+        let mut entry = ObstacleEntry::new(id, template.kind, template.tag);
+        entry.set_additional_speed(template.additional_speed);
+        entry.active = true;
+        self.obstacles.push(entry);
+        self.stats.grown += 1;
+        Some(id)
+    }
+
+    fn kind_of(&self, id: usize) -> Option<ObstacleKind>{
+        self.obstacles.iter().find(|e| e.id == id).map(|e| e.kind)
+    }
+
+    fn update_high_water(&mut self){
+        let active = self.obstacles.iter().filter(|e| e.active).count();
+        self.stats.high_water = self.stats.high_water.max(active);
+    }
+
+    fn stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.obstacles.len(),
+            active: self.obstacles.iter().filter(|e| e.active).count(),
+            ..self.stats
+        }
+    }
+
     fn deactivate(&mut self, id: usize) {
         for i in 0..self.obstacles.len(){
             if self.obstacles[i].id == id{
@@ -75,115 +203,586 @@ impl ObstaclePool {
     }
 }
 
+// Horizontal gap between obstacles spawned together as a cluster
+const CLUSTER_SPACING: f32 = 12.0;
+// How hard the manager tries to make an unclearable spawn clearable before skipping it
+const FAIR_SPAWN_REROLLS: usize = 4;
+const FAIR_SPAWN_WIDEN_STEP: f32 = 40.0;
+const FAIR_SPAWN_MAX_WIDEN: usize = 20;
+
+// Some math for calculating cactus colliders
+pub fn cactus_collider(width: f32, height: f32) -> Collider {
+    let mut hs = v2!(width / 2.0, height / 2.0);
+    let q: f32 = 0.7;
+    let col_offs = v2!(0., -hs.y * (1. - q) / 2.);
+    hs.y *= q;
+    let col_low = BoxCollider::new(hs).with_offset(col_offs);
+    let pad = 18.0 * height / 100.0;
+    let mut hs = v2!(width / 2.0 - pad, height / 2.0);
+    hs.y -= 2.;
+    let col_high = BoxCollider::new(hs);
+    Collider::new_double(col_low, col_high)
+}
+
+// Height at which a cactus sprite stands on the ground
+pub fn cactus_y(width: f32, height: f32) -> f32 {
+    let offset_y =
+        if height == 100. { // big cactus
+            if width > 100. {-2.}
+            else {-4.}
+        } else {0.};
+    GROUND_Y_COORD + height / 2.0 + offset_y
+}
+
+pub fn ptero_collider(width: f32) -> Collider {
+    Collider::new_single(BoxCollider::new(v2!(width/2. - 8., 20.)).with_offset(v2!(8., 4.)))
+}
+
+pub fn rock_collider(width: f32, height: f32) -> Collider {
+    Collider::new_single(BoxCollider::new(v2!(width / 2. - 3., height / 2. - 3.)).with_offset(v2!(0., -1.)))
+}
+
+// Height at which a rock sprite sits on the ground
+pub fn rock_y(height: f32) -> f32 {
+    GROUND_Y_COORD + height / 2.0
+}
+
+pub fn tumbleweed_collider(width: f32) -> Collider {
+    Collider::new_single(BoxCollider::new(v2!(width / 2. - 5., width / 2. - 5.)))
+}
+
+// How far above an obstacle's top edge its pickup floats, and the highest it can go
+const PICKUP_CLEARANCE: f32 = 50.0;
+const PICKUP_MAX_HEIGHT: f32 = 200.0;
+// Collected pickups float up and fade out before they're hidden
+const PICKUP_POP_TIME: f32 = 0.3;
+const PICKUP_POP_RISE: f32 = 30.0;
+
 pub struct ObstacleManager {
     pool: ObstaclePool,
-    delay: f32,
+    // Pickups reuse the pooling, but never count as obstacles
+    pickups: ObstaclePool,
+    difficulty: Difficulty,
     next_spawn_time: f32,
+    run_time: f32,
     movable_ids: Box<Vec<usize>>,
     scroll_speed: f32,
+    // Speeds and tumbleweed physics come from here
+    config: GameConfig,
+    jump_model: Option<JumpModel>,
+    chunks: ChunkLibrary,
 }
 
 impl ObstacleManager {
-    pub fn new(delay: f32, movable_ids: Vec<usize>) -> ObstacleManager {
+    pub fn new(config: &GameConfig, movable_ids: Vec<usize>) -> ObstacleManager {
+        ObstacleManager::with_pool(ObstaclePool::new(config.obstacle_pool_cap), config, movable_ids)
+    }
+    pub fn with_capacity(capacity: usize, config: &GameConfig, movable_ids: Vec<usize>) -> ObstacleManager {
+        ObstacleManager::with_pool(ObstaclePool::with_capacity(capacity, config.obstacle_pool_cap), config, movable_ids)
+    }
+    fn with_pool(pool: ObstaclePool, config: &GameConfig, movable_ids: Vec<usize>) -> ObstacleManager {
+        let difficulty = config.difficulty();
         ObstacleManager {
-            pool: ObstaclePool::new(),
-            delay,
+            pool,
+            pickups: ObstaclePool::new(config.obstacle_pool_cap),
+            scroll_speed: difficulty.scroll_speed(0., 0.),
+            difficulty,
             next_spawn_time: 0.0,
+            run_time: 0.0,
             movable_ids: Box::new(movable_ids),
-            scroll_speed: START_SCROLL_SPEED,
+            config: config.clone(),
+            jump_model: None,
+            chunks: ChunkLibrary::default(),
         }
     }
-    pub fn with_capacity(capacity: usize, delay: f32, movable_ids: Vec<usize>) -> ObstacleManager {
-        ObstacleManager {
-            pool: ObstaclePool::with_capacity(capacity),
-            delay,
-            next_spawn_time: 0.0,
-            movable_ids: Box::new(movable_ids),
-            scroll_speed: START_SCROLL_SPEED,
+    pub fn set_chunks(&mut self, chunks: ChunkLibrary){
+        self.chunks = chunks;
+    }
+    // Enables the fair-spawn check against the given dino
+    pub fn set_jump_model(&mut self, jump_model: JumpModel){
+        self.jump_model = Some(jump_model);
+    }
+    // Used when the config is reloaded mid-game. Progress through the run is kept.
+    pub fn apply_config(&mut self, config: &GameConfig){
+        self.difficulty = config.difficulty();
+        self.pool.cap = config.obstacle_pool_cap;
+        self.pickups.cap = config.obstacle_pool_cap;
+        self.config = config.clone();
+        if let Some(jump_model) = &mut self.jump_model {
+            jump_model.apply_config(config);
+        }
+        for i in 0..self.pool.obstacles.len() {
+            let speed = self.additional_speed(self.pool.obstacles[i].kind);
+            self.pool.obstacles[i].set_additional_speed(speed);
+        }
+    }
+    // How much faster than the ground each kind of obstacle moves
+    fn additional_speed(&self, kind: ObstacleKind) -> f32 {
+        match kind {
+            ObstacleKind::Cactus     => 0.,
+            ObstacleKind::Ptero      => self.config.ptero_speed,
+            ObstacleKind::Rock       => self.config.rock_speed,
+            ObstacleKind::Tumbleweed => self.config.tumbleweed_speed,
+            ObstacleKind::Pickup     => 0.,
         }
     }
-    pub fn add_cactus(&mut self, id: usize){
-        self.pool.add_cactus(id);
+    fn add(&mut self, id: usize, kind: ObstacleKind, tag: AssetTag){
+        let speed = self.additional_speed(kind);
+        self.pool.add(id, kind, tag, speed);
+    }
+    pub fn add_cactus(&mut self, id: usize, tag: AssetTag){
+        self.add(id, ObstacleKind::Cactus, tag);
+    }
+    pub fn add_ptero(&mut self, id: usize, tag: AssetTag){
+        self.add(id, ObstacleKind::Ptero, tag);
+    }
+    pub fn add_rock(&mut self, id: usize, tag: AssetTag){
+        self.add(id, ObstacleKind::Rock, tag);
     }
-    pub fn add_ptero(&mut self, id: usize){
-        self.pool.add_ptero(id);
+    pub fn add_tumbleweed(&mut self, id: usize, tag: AssetTag){
+        self.add(id, ObstacleKind::Tumbleweed, tag);
+    }
+    pub fn add_pickup(&mut self, id: usize, tag: AssetTag){
+        self.pickups.add(id, ObstacleKind::Pickup, tag, 0.);
+    }
+    // Fits the colliders, and the heights of obstacles standing on the ground, to the images
+    // the obstacles are drawn with. Needs to run again when the images change size.
+    pub fn fit_to_images(&self, ecs: &mut ECS, assets: &Assets){
+        for entry in self.pool.obstacles.iter().chain(self.pickups.obstacles.iter()) {
+            let size = assets.image_size(entry.tag);
+            let (w, h) = (size.x, size.y);
+            let collider = match entry.kind {
+                ObstacleKind::Cactus     => cactus_collider(w, h),
+                ObstacleKind::Ptero      => ptero_collider(w),
+                ObstacleKind::Rock       => rock_collider(w, h),
+                ObstacleKind::Tumbleweed => tumbleweed_collider(w),
+                ObstacleKind::Pickup     => pickup_collider(w),
+            };
+            ecs.add_component(entry.id, collider);
+            if ecs.get_component::<EndlessScroll>(entry.id).is_some() {
+                ecs.set_component(entry.id, EndlessScroll::new(w));
+            }
+            let Some(mut mov) = ecs.get_component::<Movable>(entry.id) else {continue};
+            match entry.kind {
+                ObstacleKind::Cactus => mov.pos.y = cactus_y(w, h),
+                ObstacleKind::Rock   => mov.pos.y = rock_y(h),
+                _ => continue,
+            }
+            ecs.set_component(entry.id, mov);
+        }
     }
     pub fn deactivate_all(&mut self){
         self.pool.deactivate_all();
+        self.pickups.deactivate_all();
     }
     pub fn restart(&mut self){
         self.deactivate_all();
-        self.scroll_speed = START_SCROLL_SPEED;
+        self.run_time = 0.;
+        self.scroll_speed = self.difficulty.scroll_speed(0., 0.);
     }
-    fn check_for_next_obstacle(&mut self, ecs: &mut ECS, rng: &mut Rand32, time: f32) {
+    fn check_for_next_obstacle(&mut self, ecs: &mut ECS, rng: &mut Rand32, time: f32, score: f32) {
         if time < self.next_spawn_time {return}
-        let next_cactus = self.pool.activate_next(rng).unwrap();
-        // println!("Cactus {next_cactus} activated");
+        self.update_movables_speed(ecs, self.scroll_speed);
 
-        let mut mov: Movable = ecs.get_component(next_cactus).unwrap();
-        mov.pos.x =
-            SCREEN.0 / 2.0 - ecs.get_component::<Collider>(next_cactus)
-                .unwrap()
-                .get_bound_offset(BoundType::Left)
-                .x;
-        ecs.set_component::<Movable>(next_cactus, mov);
+        // If the dino is already doomed, the new obstacle isn't what makes the layout unfair
+        let fair_before = self.is_fair(ecs, 0.);
+        let mut spawned = Vec::new();
+        for attempt in 0..=FAIR_SPAWN_REROLLS {
+            spawned = self.spawn(ecs, rng, score);
+            if !fair_before || self.spawn_is_fair(ecs, &spawned, score) {break}
+            if attempt < FAIR_SPAWN_REROLLS || !self.widen_until_fair(ecs, &spawned, score) {
+                for id in spawned.drain(..) {
+                    self.pool.deactivate(id);
+                }
+            }
+        }
 
-        self.update_movables_speed(ecs, self.scroll_speed);
-        self.next_spawn_time = time + self.delay + rng.rand_float() * 1.3;
+        // Long chunks push the next spawn back by the time it takes them to scroll in
+        let right_bounds: Vec<f32> = spawned.iter()
+            .map(|id| ecs.get_component::<Collider>(*id).unwrap().get_bound(ecs, *id, BoundType::Right).x)
+            .collect();
+        let extent = match right_bounds.first() {
+            Some(first) => right_bounds.iter().fold(*first, |a, b| a.max(*b)) - first,
+            None => 0.,
+        };
+        self.next_spawn_time = time + extent / self.scroll_speed + self.difficulty.spawn_gap(self.run_time, score, rng);
+        if let Some(first) = spawned.first() {
+            self.spawn_pickup(ecs, rng, *first);
+        }
+    }
+    // Sometimes puts a pickup above a freshly spawned obstacle, where a jump over it passes
+    fn spawn_pickup(&mut self, ecs: &mut ECS, rng: &mut Rand32, obstacle: usize) {
+        if rng.rand_float() >= self.config.pickups.chance {return}
+        let Some(kind) = self.config.pickups.weights.pick(rng) else {return};
+        let Some(id) = self.pickups.activate_tag(ecs, kind.asset_tag(), ObstacleKind::Pickup) else {return};
+        let col = ecs.get_component::<Collider>(obstacle).unwrap();
+        let obstacle_pos = ecs.get_component::<Movable>(obstacle).unwrap().pos;
+        let top = col.get_bound(ecs, obstacle, BoundType::Up).y;
+        let mut mov = ecs.get_component::<Movable>(id).unwrap();
+        mov.pos = v2!(obstacle_pos.x, (top + PICKUP_CLEARANCE).min(GROUND_Y_COORD + PICKUP_MAX_HEIGHT));
+        mov.velocity.x = -self.scroll_speed;
+        ecs.set_component(id, mov);
+        // Undo the pop of its last collection
+        if let Some(mut sprite) = ecs.get_component::<Sprite>(id) {
+            sprite.set_transform(SpriteTransform::default());
+            ecs.set_component(id, sprite);
+        }
+        ecs.add_component(id, Tweens::new());
+    }
+    fn spawn(&mut self, ecs: &mut ECS, rng: &mut Rand32, score: f32) -> Vec<usize> {
+        if rng.rand_float() < self.difficulty.chunk_probability(self.run_time, score) {
+            let tier = self.difficulty.chunk_tier(self.run_time, score);
+            if let Some(chunk) = self.chunks.pick(tier, rng).cloned() {
+                return self.spawn_chunk(ecs, rng, &chunk);
+            }
+        }
+        self.spawn_cluster(ecs, rng, score)
+    }
+    // Moves an obstacle so its left edge is at `left_x`, returns its right edge
+    fn place(ecs: &mut ECS, id: usize, left_x: f32) -> f32 {
+        let col = ecs.get_component::<Collider>(id).unwrap();
+        let mut mov: Movable = ecs.get_component(id).unwrap();
+        mov.pos.x = left_x - col.get_bound_offset(BoundType::Left).x;
+        ecs.set_component::<Movable>(id, mov);
+        mov.pos.x + col.get_bound_offset(BoundType::Right).x
+    }
+    // Activates the next obstacle (or a row of cacti) at the right edge of the screen
+    fn spawn_cluster(&mut self, ecs: &mut ECS, rng: &mut Rand32, score: f32) -> Vec<usize> {
+        let kind = self.roll_kind(rng, score);
+        let cluster_size = match kind {
+            ObstacleKind::Cactus => self.difficulty.cluster_size(self.run_time, score),
+            _ => 1,
+        };
+
+        let mut cluster = Vec::with_capacity(cluster_size);
+        let mut left_x = SCREEN.0 / 2.0;
+        for _ in 0..cluster_size {
+            let next_obstacle = self.pool.activate(ecs, rng, kind)
+                .or_else(|| self.pool.activate(ecs, rng, ObstacleKind::Cactus));
+            let Some(next_obstacle) = next_obstacle else {
+                self.pool.stats.skipped += 1;
+                break
+            };
+            // println!("Obstacle {next_obstacle} activated");
+
+            left_x = ObstacleManager::place(ecs, next_obstacle, left_x) + CLUSTER_SPACING;
+            match self.pool.kind_of(next_obstacle) {
+                Some(ObstacleKind::Ptero) => {
+                    let pattern = self.difficulty.flight_pattern(self.run_time, score, rng);
+                    Ptero::launch(ecs, next_obstacle, Ptero::random_lane(rng), pattern);
+                }
+                Some(ObstacleKind::Tumbleweed) => self.launch_tumbleweed(ecs, rng, next_obstacle),
+                _ => (),
+            }
+            cluster.push(next_obstacle);
+        }
+        cluster
+    }
+    fn roll_kind(&self, rng: &mut Rand32, score: f32) -> ObstacleKind {
+        let mut roll = rng.rand_float();
+        for (kind, probability) in [
+            (ObstacleKind::Ptero, self.difficulty.ptero_probability(self.run_time, score)),
+            (ObstacleKind::Rock, self.difficulty.rock_probability(self.run_time, score)),
+            (ObstacleKind::Tumbleweed, self.difficulty.tumbleweed_probability(self.run_time, score)),
+        ] {
+            if roll < probability {return kind}
+            roll -= probability;
+        }
+        ObstacleKind::Cactus
+    }
+    fn launch_tumbleweed(&self, ecs: &mut ECS, rng: &mut Rand32, id: usize) {
+        Bounce::launch(ecs, rng, id, self.config.tumbleweed_bounce, self.config.tumbleweed_gravity);
+    }
+    // Lays out an authored chunk starting at the right edge of the screen.
+    // Obstacles the pool can't provide are left out.
+    fn spawn_chunk(&mut self, ecs: &mut ECS, rng: &mut Rand32, chunk: &Chunk) -> Vec<usize> {
+        let mut spawned = Vec::with_capacity(chunk.obstacles.len());
+        let mut left_x = SCREEN.0 / 2.0;
+        // Spacing is authored for the starting speed. Stretching it keeps the time between obstacles the same.
+        let stretch = (self.scroll_speed / self.config.start_scroll_speed).max(1.);
+        for obstacle in chunk.obstacles.iter() {
+            left_x += obstacle.spacing * stretch;
+            let kind = match obstacle.kind {
+                ChunkObstacleKind::Ptero      => ObstacleKind::Ptero,
+                ChunkObstacleKind::Rock       => ObstacleKind::Rock,
+                ChunkObstacleKind::Tumbleweed => ObstacleKind::Tumbleweed,
+                _ => ObstacleKind::Cactus,
+            };
+            let Some(id) = self.pool.activate_tag(ecs, obstacle.kind.asset_tag(), kind) else {
+                self.pool.stats.skipped += 1;
+                continue
+            };
+            left_x = ObstacleManager::place(ecs, id, left_x);
+            match kind {
+                ObstacleKind::Ptero => {
+                    let height = obstacle.height.unwrap_or_else(|| Ptero::random_lane(rng));
+                    Ptero::launch(ecs, id, height, obstacle.pattern);
+                }
+                ObstacleKind::Tumbleweed => self.launch_tumbleweed(ecs, rng, id),
+                _ => (),
+            }
+            spawned.push(id);
+        }
+        spawned
+    }
+    fn is_fair(&self, ecs: &ECS, margin: f32) -> bool {
+        self.is_fair_at_speed(ecs, self.scroll_speed, margin)
+    }
+    // Same check, with every obstacle sped up as if the scroll speed were `speed`
+    fn is_fair_at_speed(&self, ecs: &ECS, speed: f32, margin: f32) -> bool {
+        let Some(jump_model) = self.jump_model else {return true};
+        let mut snapshots = self.snapshots(ecs);
+        for snapshot in snapshots.iter_mut() {
+            snapshot.velocity -= speed - self.scroll_speed;
+        }
+        jump_model.can_clear(ecs, &snapshots, margin)
+    }
+    // The game keeps speeding up while a spawn scrolls in, so it also has to be clearable
+    // at the speed it'll have by the time its last obstacle reaches the dino
+    fn spawn_is_fair(&self, ecs: &ECS, spawned: &[usize], score: f32) -> bool {
+        let right_edge = spawned.iter()
+            .map(|id| ecs.get_component::<Collider>(*id).unwrap().get_bound(ecs, *id, BoundType::Right).x)
+            .fold(SCREEN.0 / 2., f32::max);
+        let arrival = (right_edge + SCREEN.0 / 2.) / self.scroll_speed.max(1.);
+        let speed_then = self.difficulty.scroll_speed(self.run_time + arrival, score).max(self.scroll_speed);
+        self.is_fair(ecs, FAIR_SPAWN_MARGIN) && self.is_fair_at_speed(ecs, speed_then, FAIR_SPAWN_MARGIN)
+    }
+    // Pushes the spawned obstacles further right until the gap before them can be cleared
+    fn widen_until_fair(&self, ecs: &mut ECS, cluster: &[usize], score: f32) -> bool {
+        for _ in 0..FAIR_SPAWN_MAX_WIDEN {
+            for id in cluster {
+                let mut mov = ecs.get_component::<Movable>(*id).unwrap();
+                mov.pos.x += FAIR_SPAWN_WIDEN_STEP;
+                ecs.set_component(*id, mov);
+                if self.pool.kind_of(*id) == Some(ObstacleKind::Ptero) {
+                    Ptero::follow_pattern(ecs, *id);
+                }
+            }
+            if self.spawn_is_fair(ecs, cluster, score) {return true}
+        }
+        false
+    }
+    // Positions and velocities of every active obstacle
+    pub fn snapshots(&self, ecs: &ECS) -> Vec<ObstacleSnapshot> {
+        self.pool.obstacles.iter()
+            .filter(|entry| entry.active)
+            .map(|entry| ObstacleSnapshot::from_entity(ecs, entry.id))
+            .collect()
     }
     fn update_movables_speed(&self, ecs: &mut ECS, new_vel: f32){
-        if self.scroll_speed >= MAX_SCROLL_SPEED {return}
         for id in self.movable_ids.iter() {
             let mut mov = ecs.get_component::<Movable>(*id).unwrap();
             mov.velocity.x = -new_vel;
             ecs.set_component(*id, mov);
         }
-        for entry in self.pool.obstacles.iter() {
+        for entry in self.pool.obstacles.iter().chain(self.pickups.obstacles.iter()) {
             let id = entry.id;
             let mut mov = ecs.get_component::<Movable>(id).unwrap();
             mov.velocity.x = -(new_vel + entry.additional_speed);
             ecs.set_component(id, mov);
         }
     }
-    fn update_scroll_speed(&mut self, dt: f32){
-        if self.scroll_speed >= MAX_SCROLL_SPEED {return}
-        self.scroll_speed += dt * 5.5;
+    fn update_scroll_speed(&mut self, dt: f32, score: f32){
+        self.run_time += dt;
+        self.scroll_speed = self.difficulty.scroll_speed(self.run_time, score);
     }
-    pub fn update(&mut self, ecs: &mut ECS, rng: &mut Rand32, time: f32, dt: f32){
-        for i in 0..self.pool.obstacles.len() {
-            if self.pool.obstacles[i].active{
-                let id = self.pool.obstacles[i].id;
+    pub fn update(&mut self, ecs: &mut ECS, rng: &mut Rand32, time: f32, dt: f32, score: f32){
+        self.check_for_next_obstacle(ecs, rng, time, score);
+        ObstacleManager::move_pool(&mut self.pool, ecs, dt);
+        ObstacleManager::move_pool(&mut self.pickups, ecs, dt);
+        self.update_scroll_speed(dt, score);
+    }
+    // Moves every active entry, deactivating the ones that left the screen
+    fn move_pool(pool: &mut ObstaclePool, ecs: &mut ECS, dt: f32){
+        for i in 0..pool.obstacles.len() {
+            if pool.obstacles[i].active{
+                let id = pool.obstacles[i].id;
                 let col = ecs.get_component::<Collider>(id).unwrap();
                 let right_bound = col.get_bound(ecs, id, BoundType::Right).x;
                 if right_bound < - SCREEN.0 / 2.0 {
-                    self.pool.deactivate(id);
+                    pool.deactivate(id);
                 }
                 else{
                     Movable::update_pos(ecs, id, dt);
+                    match pool.obstacles[i].kind {
+                        ObstacleKind::Ptero      => Ptero::follow_pattern(ecs, id),
+                        ObstacleKind::Tumbleweed => Bounce::bounce_off_ground(ecs, id),
+                        _ => (),
+                    }
                 }
             }
         }
-        self.check_for_next_obstacle(ecs, rng, time);
-        self.update_scroll_speed(dt);
+    }
+    pub fn scroll_speed(&self) -> f32 {
+        self.scroll_speed
     }
     pub fn check_collision(&self, ecs: &ECS, entity_id: usize) -> bool{
+        self.colliding_obstacle(ecs, entity_id).is_some()
+    }
+    pub fn colliding_obstacle(&self, ecs: &ECS, entity_id: usize) -> Option<usize>{
         for i in 0..self.pool.obstacles.len() {
             if self.pool.obstacles[i].active{
                 if Collider::check_entity_collision(ecs, entity_id, self.pool.obstacles[i].id) {
-                    return true;
+                    return Some(self.pool.obstacles[i].id);
                 }
             }
         }
-        false
+        None
+    }
+    // Takes an obstacle out of play, e.g. after a shield absorbed it
+    pub fn remove(&mut self, ecs: &mut ECS, id: usize){
+        self.pool.deactivate(id);
+        ObstacleManager::hide(ecs, id);
+    }
+    fn hide(ecs: &mut ECS, id: usize){
+        let mut mov = ecs.get_component::<Movable>(id).unwrap();
+        mov.pos.x = SCREEN.0 + 50.;
+        ecs.set_component(id, mov);
+    }
+    // Deactivates the pickups the entity touches and returns what they were. They pop out
+    // and fire `TweenEvent::PICKUP_COLLECTED` once they can be hidden.
+    pub fn collect_pickups(&mut self, ecs: &mut ECS, entity_id: usize) -> Vec<PickupKind>{
+        let mut collected = Vec::new();
+        for i in 0..self.pickups.obstacles.len() {
+            let id = self.pickups.obstacles[i].id;
+            if !self.pickups.obstacles[i].active || !Collider::check_entity_collision(ecs, entity_id, id) {continue}
+            self.pickups.deactivate(id);
+            let y = ecs.get_component::<Movable>(id).unwrap().pos.y;
+            let pop = Tweens::new()
+                .with(TweenTarget::PosY,  Tween::new(y, y + PICKUP_POP_RISE, PICKUP_POP_TIME, Ease::QuadOut))
+                .with(TweenTarget::Scale, Tween::new(1., 1.5, PICKUP_POP_TIME, Ease::QuadOut))
+                .with(TweenTarget::Alpha, Tween::new(1., 0., PICKUP_POP_TIME, Ease::QuadIn).with_event(TweenEvent::PICKUP_COLLECTED));
+            ecs.add_component(id, pop);
+            collected.push(ecs.get_component::<Pickup>(id).unwrap().kind);
+        }
+        collected
+    }
+    // Hides the collected pickups that are done popping out
+    pub fn hide_collected(&self, ecs: &mut ECS){
+        for entry in self.pickups.obstacles.iter().filter(|entry| !entry.active) {
+            let popped = ecs.get_component::<Tweens>(entry.id).is_some_and(|tweens| tweens.fired(TweenEvent::PICKUP_COLLECTED));
+            if popped {ObstacleManager::hide(ecs, entry.id)}
+        }
+    }
+    // Pulls nearby coins towards `target`
+    pub fn attract_coins(&self, ecs: &mut ECS, target: Vec2, radius: f32, speed: f32, dt: f32){
+        for entry in self.pickups.obstacles.iter().filter(|entry| entry.active) {
+            if ecs.get_component::<Pickup>(entry.id).unwrap().kind != PickupKind::Coin {continue}
+            let mut mov = ecs.get_component::<Movable>(entry.id).unwrap();
+            let to_target = target - mov.pos;
+            if to_target.length() > radius {continue}
+            mov.pos += to_target.clamp_length_max(speed * dt);
+            ecs.set_component(entry.id, mov);
+        }
     }
     pub fn ids(&self) -> Vec<usize>{
-        let mut res = Vec::with_capacity(self.pool.obstacles.len());
-        for entry in &self.pool.obstacles {
+        let mut res = Vec::with_capacity(self.pool.obstacles.len() + self.pickups.obstacles.len());
+        for entry in self.pool.obstacles.iter().chain(self.pickups.obstacles.iter()) {
             res.push(entry.id);
         }
         res
     }
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
     pub fn id(&self, ind: usize) -> usize {
         self.pool.obstacles[ind].id
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // One of every obstacle the game starts with: six cacti, two pteros, a rock and a tumbleweed
+    fn manager(config: &GameConfig) -> (ECS, ObstacleManager) {
+        let mut ecs = ECS::new();
+        let mut manager = ObstacleManager::new(config, vec![]);
+        let sizes = [(34., 70.), (68., 70.), (102., 70.), (50., 100.), (100., 100.), (150., 100.)];
+        for (tag, (w, h)) in AssetTag::cactus_tags().into_iter().zip(sizes) {
+            let cactus = ecs.new_entity();
+            ecs.add_component(cactus, Movable::new(v2!(SCREEN.0 + 50., cactus_y(w, h)), v2!(), v2!()));
+            ecs.add_component(cactus, cactus_collider(w, h));
+            manager.add_cactus(cactus, tag);
+        }
+        for _ in 0..2 {
+            let ptero = ecs.new_entity();
+            ecs.add_component(ptero, Movable::new(v2!(SCREEN.0 + 50., GROUND_Y_COORD + 40.), v2!(), v2!()));
+            ecs.add_component(ptero, ptero_collider(92.));
+            ecs.add_component(ptero, Ptero::new());
+            manager.add_ptero(ptero, AssetTag::PTERO_ANIM);
+        }
+        let rock = ecs.new_entity();
+        ecs.add_component(rock, Movable::new(v2!(SCREEN.0 + 50., rock_y(28.)), v2!(), v2!()));
+        ecs.add_component(rock, rock_collider(36., 28.));
+        manager.add_rock(rock, AssetTag::ROCK_ANIM);
+        let tumbleweed = ecs.new_entity();
+        ecs.add_component(tumbleweed, Movable::new(v2!(SCREEN.0 + 50., GROUND_Y_COORD), v2!(), v2!()));
+        ecs.add_component(tumbleweed, tumbleweed_collider(44.));
+        ecs.add_component(tumbleweed, Bounce::new(config.tumbleweed_bounce));
+        manager.add_tumbleweed(tumbleweed, AssetTag::TUMBLEWEED_ANIM);
+        (ecs, manager)
+    }
+    #[test]
+    fn pool_grows_up_to_cap(){
+        let config = GameConfig { obstacle_pool_cap: 14, ..GameConfig::default() };
+        let (mut ecs, mut manager) = manager(&config);
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        // Nothing moves, so every spawn needs a fresh obstacle
+        for i in 0..50 {
+            manager.update(&mut ecs, &mut rng, i as f32 * 10., 0., 0.);
+        }
+        let stats = manager.pool_stats();
+        assert_eq!(stats.size, 14);
+        assert_eq!(stats.grown, 4);
+        assert!(stats.skipped > 0);
+        assert!(stats.high_water <= stats.size);
+    }
+    #[test]
+    fn dense_spawns_reuse_the_grown_pool(){
+        let mut difficulty = Difficulty::preset(DifficultyPreset::Normal, &GameConfig::default());
+        difficulty.spawn_gap_min = DifficultyCurve::Constant { value: 0.2 };
+        difficulty.spawn_gap_range = DifficultyCurve::Constant { value: 0. };
+        difficulty.cluster_size = DifficultyCurve::Constant { value: 3. };
+        let config = GameConfig {
+            difficulty: DifficultyPreset::Custom,
+            custom_difficulty: Some(difficulty),
+            obstacle_pool_cap: 24,
+            ..GameConfig::default()
+        };
+        let (mut ecs, mut manager) = manager(&config);
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        let dt = 1.0 / DESIRED_FPS as f32;
+        for tick in 0..(60. / dt) as usize {
+            manager.update(&mut ecs, &mut rng, tick as f32 * dt, dt, 0.);
+        }
+        // A minute of spawns every fifth of a second only fits under the cap if
+        // obstacles that left the screen are reused
+        let stats = manager.pool_stats();
+        assert!(stats.grown > 0);
+        assert_eq!(stats.skipped, 0);
+        assert!(stats.high_water <= stats.size && stats.size <= config.obstacle_pool_cap);
+    }
+    #[test]
+    fn grown_pickups_can_be_collected(){
+        let (mut ecs, mut manager) = manager(&GameConfig::default());
+        let coin = ecs.new_entity();
+        ecs.add_component(coin, Movable::new(v2!(), v2!(), v2!()));
+        ecs.add_component(coin, pickup_collider(30.));
+        ecs.add_component(coin, Pickup::new(PickupKind::Coin));
+        manager.add_pickup(coin, AssetTag::PICKUP_COIN);
+        let dino = ecs.new_entity();
+        ecs.add_component(dino, Movable::new(v2!(), v2!(), v2!()));
+        ecs.add_component(dino, DinoController::collider());
+
+        // The first coin is still out, so the second one is a new entity
+        assert_eq!(manager.pickups.activate_tag(&mut ecs, AssetTag::PICKUP_COIN, ObstacleKind::Pickup), Some(coin));
+        let grown = manager.pickups.activate_tag(&mut ecs, AssetTag::PICKUP_COIN, ObstacleKind::Pickup).unwrap();
+        assert_ne!(grown, coin);
+        assert_eq!(ecs.get_component::<Pickup>(grown), Some(Pickup::new(PickupKind::Coin)));
+
+        let mut mov = ecs.get_component::<Movable>(coin).unwrap();
+        mov.pos.x = 500.;
+        ecs.set_component(coin, mov);
+        manager.attract_coins(&mut ecs, v2!(), 100., 10., 1.);
+        assert_eq!(manager.collect_pickups(&mut ecs, dino), [PickupKind::Coin]);
+    }
+}
@@ -0,0 +1,169 @@
+// Scenery layers scrolling at their own fraction of the game speed, loaded from `resources/background.toml`
+use crate::prelude::*;
+use serde::Deserialize;
+
+pub const BACKGROUND_PATH: &str = "/background.toml";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct LayerDef {
+    pub name: String,
+    // Image id from the asset manifest
+    pub image: String,
+    // Draw order, lowest first. Layers below 0 are drawn behind the ground and obstacles, the rest in front.
+    pub z: i32,
+    // Fraction of the scroll speed the layer moves at
+    pub speed: f32,
+    // Copies of the image on the layer
+    #[serde(default = "default_count")]
+    pub count: usize,
+    // Random gap between neighbouring copies, in pixels
+    #[serde(default)]
+    pub spacing: (f32, f32),
+    // Random height of each copy, in world coordinates
+    pub height: (f32, f32),
+}
+
+fn default_count() -> usize {
+    1
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct BackgroundDef {
+    #[serde(default, rename = "layer")]
+    pub layers: Vec<LayerDef>,
+}
+
+// A single cloud drifting at half speed
+impl Default for BackgroundDef {
+    fn default() -> Self {
+        BackgroundDef {
+            layers: vec![LayerDef {
+                name: "clouds".to_string(),
+                image: "cloud".to_string(),
+                z: -10,
+                speed: 0.5,
+                count: 1,
+                spacing: (0., 0.),
+                height: (200., 200.),
+            }],
+        }
+    }
+}
+
+impl BackgroundDef {
+    // Layers come out sorted by z
+    pub fn from_toml(src: &str) -> Result<BackgroundDef, toml::de::Error> {
+        let mut def: BackgroundDef = toml::from_str(src)?;
+        def.layers.retain(|layer| layer.count > 0);
+        def.layers.sort_by_key(|layer| layer.z);
+        Ok(def)
+    }
+
+    // Falls back to the default clouds if the file is missing or can't be parsed
+    pub fn load(ctx: &mut Context) -> BackgroundDef {
+        load_toml_or_default(ctx, BACKGROUND_PATH, BackgroundDef::from_toml)
+    }
+}
+
+struct ParallaxLayer {
+    def: LayerDef,
+    width: f32,
+    ids: Vec<usize>,
+}
+
+impl ParallaxLayer {
+    // The rightmost point of the layer, or the right edge of the screen if the layer ends before it
+    fn right_edge(&self, ecs: &ECS) -> f32 {
+        self.ids.iter()
+            .map(|id| ecs.get_component::<Movable>(*id).unwrap().pos.x + self.width / 2.)
+            .fold(SCREEN.0 / 2., f32::max)
+    }
+}
+
+pub struct Parallax {
+    layers: Vec<ParallaxLayer>,
+}
+
+impl Parallax {
+    // Creates an entity for every copy on every layer, spread out from the left edge of the screen
+    pub fn new(ecs: &mut ECS, assets: &Assets, rng: &mut Rand32, def: BackgroundDef) -> Parallax {
+        let layers = def.layers.into_iter().map(|def| {
+            let tag = AssetTag::new(&def.image);
+            let width = assets.get_image(tag).width() as f32;
+            let mut left = -SCREEN.0 / 2.;
+            let ids = (0..def.count).map(|_| {
+                left += random_in(def.spacing, rng);
+                let id = ecs.new_entity();
+                ecs.add_component(id, Movable::new(v2!(left + width / 2., random_in(def.height, rng)), v2!(), v2!()));
+                ecs.add_component(id, Sprite::new(tag));
+                ecs.add_component(id, RenderLayer::scenery(def.z));
+                left += width;
+                id
+            }).collect();
+            ParallaxLayer { def, width, ids }
+        }).collect();
+        Parallax { layers }
+    }
+
+    // Copies that leave the screen on the left are moved past the rightmost one, at a new height
+    pub fn update(&self, ecs: &mut ECS, rng: &mut Rand32, scroll_speed: f32, dt: f32) {
+        for layer in self.layers.iter() {
+            for id in layer.ids.iter() {
+                let mut mov = ecs.get_component::<Movable>(*id).unwrap();
+                mov.velocity.x = -scroll_speed * layer.def.speed;
+                ecs.set_component(*id, mov);
+                Movable::update_pos(ecs, *id, dt);
+            }
+            for id in layer.ids.iter() {
+                let mut mov = ecs.get_component::<Movable>(*id).unwrap();
+                if mov.pos.x + layer.width / 2. >= -SCREEN.0 / 2. {continue}
+                mov.pos.x = layer.right_edge(ecs) + random_in(layer.def.spacing, rng) + layer.width / 2.;
+                mov.pos.y = random_in(layer.def.height, rng);
+                ecs.set_component(*id, mov);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn layers_are_sorted_by_z(){
+        let def = BackgroundDef::from_toml(r#"
+            [[layer]]
+            name = "grass"
+            image = "grass"
+            z = 10
+            speed = 1.3
+            count = 4
+            spacing = [100.0, 300.0]
+            height = [-60.0, -30.0]
+
+            [[layer]]
+            name = "empty"
+            image = "cloud"
+            z = 0
+            speed = 1.0
+            count = 0
+            height = [0.0, 0.0]
+
+            [[layer]]
+            name = "mountains"
+            image = "mountains"
+            z = -30
+            speed = 0.1
+            height = [30.0, 30.0]
+        "#).unwrap();
+        let names: Vec<&str> = def.layers.iter().map(|layer| layer.name.as_str()).collect();
+        assert_eq!(names, ["mountains", "grass"]);
+        assert_eq!(def.layers[0].count, 1);
+        assert_eq!(def.layers[0].spacing, (0., 0.));
+        assert_eq!(def.layers[1].spacing, (100., 300.));
+    }
+    #[test]
+    fn shipped_background_parses(){
+        let def = BackgroundDef::from_toml(include_str!("../resources/background.toml")).unwrap();
+        assert!(!def.layers.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+// Dust, landing puffs and other short-lived effects. Particles aren't entities: they live in
+// a fixed-size pool and are drawn as one mesh, so a busy frame costs a single draw call.
+use crate::prelude::*;
+use std::f32::consts::PI;
+
+const PARTICLE_GRAY: f32 = 83. / 255.;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParticleEffect {
+    // Particles per burst
+    pub count: u32,
+    // Seconds each particle lives, picked between these
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    // Launch direction in degrees, 0 is right and 90 is up
+    pub angle: (f32, f32),
+    pub gravity: f32,
+    // How much the particles are carried along with the scrolling ground, from 0 to 1
+    pub scroll: f32,
+    // Color at the start and the end of the particle's life, alpha included
+    pub color: (Color, Color),
+    // Size in pixels at the start, middle and end of the particle's life
+    pub size: (f32, f32, f32),
+}
+
+impl ParticleEffect {
+    // Kicked up behind a running dino
+    pub const DUST: ParticleEffect = ParticleEffect {
+        count: 1,
+        lifetime: (0.25, 0.45),
+        speed: (30., 90.),
+        angle: (110., 170.),
+        gravity: -200.,
+        scroll: 1.,
+        color: (Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.6), Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.)),
+        size: (4., 4., 2.),
+    };
+    // Puffed up by each footstep
+    pub const STEP: ParticleEffect = ParticleEffect {
+        count: 3,
+        lifetime: (0.15, 0.3),
+        speed: (40., 110.),
+        angle: (100., 160.),
+        gravity: -300.,
+        scroll: 1.,
+        color: (Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.7), Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.)),
+        size: (4., 3., 2.),
+    };
+    pub const JUMP: ParticleEffect = ParticleEffect {
+        count: 6,
+        lifetime: (0.2, 0.35),
+        speed: (60., 160.),
+        angle: (100., 175.),
+        gravity: -400.,
+        scroll: 1.,
+        color: (Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.8), Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.)),
+        size: (4., 4., 2.),
+    };
+    pub const LANDING: ParticleEffect = ParticleEffect {
+        count: 12,
+        lifetime: (0.3, 0.5),
+        speed: (80., 220.),
+        angle: (5., 175.),
+        gravity: -700.,
+        scroll: 1.,
+        color: (Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.9), Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.)),
+        size: (6., 4., 2.),
+    };
+    pub const DEATH: ParticleEffect = ParticleEffect {
+        count: 40,
+        lifetime: (0.6, 1.1),
+        speed: (150., 450.),
+        angle: (0., 360.),
+        gravity: -900.,
+        scroll: 0.,
+        color: (Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 1.), Color::new(PARTICLE_GRAY, PARTICLE_GRAY, PARTICLE_GRAY, 0.)),
+        size: (8., 6., 2.),
+    };
+
+    fn size_at(&self, t: f32) -> f32 {
+        let (start, mid, end) = self.size;
+        if t < 0.5 {start + (mid - start) * t * 2.} else {mid + (end - mid) * (t - 0.5) * 2.}
+    }
+    fn color_at(&self, t: f32) -> Color {
+        let (a, b) = self.color;
+        Color::new(
+            a.r + (b.r - a.r) * t,
+            a.g + (b.g - a.g) * t,
+            a.b + (b.b - a.b) * t,
+            a.a + (b.a - a.a) * t,
+        )
+    }
+}
+
+// Emits particles from an entity as its `Movable` runs, jumps and lands
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParticleEmitter {
+    offset: Vec2,
+    // Emitted continuously, `rate` per second, while the entity is on the ground
+    trail: Option<ParticleEffect>,
+    rate: f32,
+    on_jump: Option<ParticleEffect>,
+    on_land: Option<ParticleEffect>,
+    // Burst when the entity's animation fires a footstep
+    on_step: Option<ParticleEffect>,
+    timer: f32,
+    was_on_ground: bool,
+}
+
+impl ParticleEmitter {
+    pub fn new(offset: Vec2) -> ParticleEmitter {
+        ParticleEmitter {
+            offset,
+            trail: None,
+            rate: 0.,
+            on_jump: None,
+            on_land: None,
+            on_step: None,
+            timer: 0.,
+            was_on_ground: true,
+        }
+    }
+    pub fn with_trail(mut self, effect: ParticleEffect, rate: f32) -> ParticleEmitter {
+        self.trail = Some(effect);
+        self.rate = rate;
+        self
+    }
+    pub fn with_jump(mut self, effect: ParticleEffect) -> ParticleEmitter {
+        self.on_jump = Some(effect);
+        self
+    }
+    pub fn with_land(mut self, effect: ParticleEffect) -> ParticleEmitter {
+        self.on_land = Some(effect);
+        self
+    }
+    pub fn with_step(mut self, effect: ParticleEffect) -> ParticleEmitter {
+        self.on_step = Some(effect);
+        self
+    }
+    pub fn set_offset(&mut self, offset: Vec2) {
+        self.offset = offset;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Particle {
+    pos: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    effect: ParticleEffect,
+}
+
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    // Particles past this many are dropped
+    cap: usize,
+}
+
+impl ParticleSystem {
+    pub fn new(cap: usize) -> ParticleSystem {
+        ParticleSystem {
+            particles: Vec::with_capacity(cap),
+            cap,
+        }
+    }
+    pub fn set_cap(&mut self, cap: usize) {
+        self.cap = cap;
+        self.particles.truncate(cap);
+    }
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    fn spawn(&mut self, effect: &ParticleEffect, pos: Vec2, rng: &mut Rand32) {
+        if self.particles.len() >= self.cap {return}
+        let angle = random_in(effect.angle, rng) * PI / 180.;
+        let speed = random_in(effect.speed, rng);
+        self.particles.push(Particle {
+            pos,
+            velocity: v2!(angle.cos(), angle.sin()) * speed,
+            age: 0.,
+            lifetime: random_in(effect.lifetime, rng).max(0.01),
+            effect: *effect,
+        });
+    }
+    pub fn burst(&mut self, effect: &ParticleEffect, pos: Vec2, rng: &mut Rand32) {
+        for _ in 0..effect.count {
+            self.spawn(effect, pos, rng);
+        }
+    }
+
+    // Runs every `ParticleEmitter` in the ECS
+    pub fn emit(&mut self, ecs: &mut ECS, rng: &mut Rand32, dt: f32) {
+        let ids: Vec<usize> = match ecs.borrow_component_vec::<ParticleEmitter>() {
+            Some(emitters) => emitters.iter().enumerate().filter(|(_, e)| e.is_some()).map(|(id, _)| id).collect(),
+            None => return,
+        };
+        for id in ids {
+            let mut emitter = ecs.get_component::<ParticleEmitter>(id).unwrap();
+            let mov = match ecs.get_component::<Movable>(id) {
+                Some(mov) => mov,
+                None => continue,
+            };
+            let pos = mov.pos + emitter.offset;
+            if mov.on_ground != emitter.was_on_ground {
+                let effect = if mov.on_ground {emitter.on_land} else {emitter.on_jump};
+                if let Some(effect) = effect {self.burst(&effect, pos, rng)}
+                emitter.was_on_ground = mov.on_ground;
+            }
+            let stepped = ecs.get_component::<Animation>(id).and_then(|anim| anim.event()) == Some(AnimEvent::FOOTSTEP);
+            if let (Some(step), true) = (emitter.on_step, stepped) {
+                self.burst(&step, pos, rng);
+            }
+            if let (Some(trail), true) = (emitter.trail, mov.on_ground) {
+                emitter.timer += emitter.rate * dt;
+                while emitter.timer >= 1. {
+                    emitter.timer -= 1.;
+                    self.burst(&trail, pos, rng);
+                }
+            }
+            ecs.set_component(id, emitter);
+        }
+    }
+
+    // Moves the particles and frees the ones that ran out of time
+    pub fn simulate(&mut self, scroll_speed: f32, dt: f32) {
+        for particle in self.particles.iter_mut() {
+            particle.age += dt;
+            particle.velocity.y += particle.effect.gravity * dt;
+            particle.pos += particle.velocity * dt;
+            particle.pos.x -= scroll_speed * particle.effect.scroll * dt;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    pub fn update(&mut self, ecs: &mut ECS, rng: &mut Rand32, scroll_speed: f32, dt: f32) {
+        self.emit(ecs, rng, dt);
+        self.simulate(scroll_speed, dt);
+    }
+
+    pub fn draw(&self, ctx: &mut Context, screen_size: Screen2) -> GameResult {
+        if self.particles.is_empty() {return Ok(())}
+        let mut mesh = graphics::MeshBuilder::new();
+        for particle in self.particles.iter() {
+            let t = particle.age / particle.lifetime;
+            let size = particle.effect.size_at(t).max(1.).floor();
+            let pos = world_to_screen_coords(screen_size, particle.pos);
+            let bounds = graphics::Rect::new((pos.x - size / 2.).floor(), (pos.y - size / 2.).floor(), size, size);
+            mesh.rectangle(graphics::DrawMode::fill(), bounds, particle.effect.color_at(t))?;
+        }
+        let mesh = mesh.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn particle_count_is_capped(){
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        let mut particles = ParticleSystem::new(30);
+        particles.burst(&ParticleEffect::DEATH, v2!(), &mut rng);
+        assert_eq!(particles.len(), 30);
+        particles.simulate(0., ParticleEffect::DEATH.lifetime.1);
+        assert!(particles.is_empty());
+    }
+    #[test]
+    fn emitter_puffs_on_landing(){
+        let mut rng = Rand32::new(RNG_DEFAULT_SEED);
+        let mut ecs = ECS::new();
+        let id = ecs.new_entity();
+        let mut mov = Movable::new(v2!(), v2!(), v2!());
+        mov.on_ground = true;
+        ecs.add_component(id, mov);
+        ecs.add_component(id, ParticleEmitter::new(v2!()).with_land(ParticleEffect::LANDING));
+        let mut particles = ParticleSystem::new(100);
+        particles.emit(&mut ecs, &mut rng, 0.1);
+        assert!(particles.is_empty());
+        mov.on_ground = false;
+        ecs.set_component(id, mov);
+        particles.emit(&mut ecs, &mut rng, 0.1);
+        assert!(particles.is_empty());
+        mov.on_ground = true;
+        ecs.set_component(id, mov);
+        particles.emit(&mut ecs, &mut rng, 0.1);
+        assert_eq!(particles.len(), ParticleEffect::LANDING.count as usize);
+    }
+}
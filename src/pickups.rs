@@ -0,0 +1,274 @@
+// Collectibles spawned alongside obstacles, and the timed effects they give
+use crate::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+// Coins turn around this many times per second, like they're spinning
+const COIN_SPIN_RATE: f32 = 1.2;
+// Power-ups grow and shrink by this much, this many times per second
+const PULSE_SIZE: f32 = 0.1;
+const PULSE_RATE: f32 = 2.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickupKind {
+    Coin,
+    Shield,
+    SlowMotion,
+    DoubleJump,
+    Magnet,
+}
+
+impl PickupKind {
+    pub const ALL: [PickupKind; 5] = [
+        PickupKind::Coin,
+        PickupKind::Shield,
+        PickupKind::SlowMotion,
+        PickupKind::DoubleJump,
+        PickupKind::Magnet,
+    ];
+    pub fn asset_tag(&self) -> AssetTag {
+        match self {
+            PickupKind::Coin       => AssetTag::PICKUP_COIN,
+            PickupKind::Shield     => AssetTag::PICKUP_SHIELD,
+            PickupKind::SlowMotion => AssetTag::PICKUP_SLOW_MOTION,
+            PickupKind::DoubleJump => AssetTag::PICKUP_DOUBLE_JUMP,
+            PickupKind::Magnet     => AssetTag::PICKUP_MAGNET,
+        }
+    }
+    pub fn sound_tag(&self) -> AssetTag {
+        match self {
+            PickupKind::Coin => AssetTag::COIN_SOUND,
+            _ => AssetTag::POWER_UP_SOUND,
+        }
+    }
+}
+
+// Marks an entity as something the dino can collect
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pickup {
+    pub kind: PickupKind,
+}
+
+impl Pickup {
+    pub fn new(kind: PickupKind) -> Pickup {
+        Pickup { kind }
+    }
+}
+
+impl Update for Pickup {
+    fn update(ecs: &mut ECS, _assets: &Assets, _rng: &mut Rand32, entity_id: usize, time: f32, _dt: f32) {
+        let pickup = ecs.get_component::<Pickup>(entity_id).unwrap();
+        let Some(mut sprite) = ecs.get_component::<Sprite>(entity_id) else {return};
+        let transform = sprite.transform_mut();
+        match pickup.kind {
+            PickupKind::Coin => transform.scale.x = (time * COIN_SPIN_RATE * TAU).cos(),
+            _                => transform.scale = Vec2::splat(1. + PULSE_SIZE * (time * PULSE_RATE * TAU).sin()),
+        }
+        ecs.set_component(entity_id, sprite);
+    }
+}
+
+pub fn pickup_collider(width: f32) -> Collider {
+    Collider::new_single(BoxCollider::new(v2!(width / 2., width / 2.)))
+}
+
+// Relative chances of each pickup
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PickupWeights {
+    pub coin: f32,
+    pub shield: f32,
+    pub slow_motion: f32,
+    pub double_jump: f32,
+    pub magnet: f32,
+}
+
+impl Default for PickupWeights {
+    fn default() -> Self {
+        PickupWeights {
+            coin: 6.,
+            shield: 1.,
+            slow_motion: 1.,
+            double_jump: 1.,
+            magnet: 1.,
+        }
+    }
+}
+
+impl PickupWeights {
+    fn weight(&self, kind: PickupKind) -> f32 {
+        match kind {
+            PickupKind::Coin       => self.coin,
+            PickupKind::Shield     => self.shield,
+            PickupKind::SlowMotion => self.slow_motion,
+            PickupKind::DoubleJump => self.double_jump,
+            PickupKind::Magnet     => self.magnet,
+        }.max(0.)
+    }
+    pub fn pick(&self, rng: &mut Rand32) -> Option<PickupKind> {
+        let total: f32 = PickupKind::ALL.iter().map(|kind| self.weight(*kind)).sum();
+        if total <= 0. {return None}
+        let mut roll = rng.rand_float() * total;
+        for kind in PickupKind::ALL {
+            if roll < self.weight(kind) {return Some(kind)}
+            roll -= self.weight(kind);
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PickupConfig {
+    // Chance that an obstacle spawn comes with a pickup above it
+    pub chance: f32,
+    // Points added to the score per coin
+    pub coin_value: f32,
+    // Effect durations in seconds
+    pub shield_duration: f32,
+    pub slow_motion_duration: f32,
+    pub double_jump_duration: f32,
+    pub magnet_duration: f32,
+    // How much slow motion slows the game down
+    pub slow_motion_scale: f32,
+    // Coins closer than this fly to the dino while the magnet is active
+    pub magnet_radius: f32,
+    pub magnet_speed: f32,
+    pub weights: PickupWeights,
+}
+
+impl Default for PickupConfig {
+    fn default() -> Self {
+        PickupConfig {
+            chance: 0.3,
+            coin_value: 25.,
+            shield_duration: 15.,
+            slow_motion_duration: 5.,
+            double_jump_duration: 10.,
+            magnet_duration: 10.,
+            slow_motion_scale: 0.6,
+            magnet_radius: 350.,
+            magnet_speed: 1500.,
+            weights: PickupWeights::default(),
+        }
+    }
+}
+
+impl PickupConfig {
+    pub fn validated(mut self) -> PickupConfig {
+        self.chance               = self.chance.clamp(0., 1.);
+        self.coin_value           = self.coin_value.max(0.);
+        self.shield_duration      = self.shield_duration.clamp(0., 120.);
+        self.slow_motion_duration = self.slow_motion_duration.clamp(0., 120.);
+        self.double_jump_duration = self.double_jump_duration.clamp(0., 120.);
+        self.magnet_duration      = self.magnet_duration.clamp(0., 120.);
+        self.slow_motion_scale    = self.slow_motion_scale.clamp(0.1, 1.);
+        self.magnet_radius        = self.magnet_radius.clamp(0., 2000.);
+        self.magnet_speed         = self.magnet_speed.clamp(0., 10000.);
+        self
+    }
+    pub fn duration(&self, kind: PickupKind) -> f32 {
+        match kind {
+            PickupKind::Coin       => 0.,
+            PickupKind::Shield     => self.shield_duration,
+            PickupKind::SlowMotion => self.slow_motion_duration,
+            PickupKind::DoubleJump => self.double_jump_duration,
+            PickupKind::Magnet     => self.magnet_duration,
+        }
+    }
+}
+
+// Collected coins and the seconds left on each power-up
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PowerUps {
+    coins: u32,
+    shield: f32,
+    slow_motion: f32,
+    double_jump: f32,
+    magnet: f32,
+}
+
+impl PowerUps {
+    pub fn new() -> PowerUps {
+        PowerUps::default()
+    }
+    fn timer(&mut self, kind: PickupKind) -> Option<&mut f32> {
+        match kind {
+            PickupKind::Coin       => None,
+            PickupKind::Shield     => Some(&mut self.shield),
+            PickupKind::SlowMotion => Some(&mut self.slow_motion),
+            PickupKind::DoubleJump => Some(&mut self.double_jump),
+            PickupKind::Magnet     => Some(&mut self.magnet),
+        }
+    }
+    // Returns the bonus points for the pickup. Collecting a running power-up restarts its timer.
+    pub fn collect(&mut self, kind: PickupKind, config: &PickupConfig) -> f32 {
+        match self.timer(kind) {
+            Some(timer) => {
+                *timer = config.duration(kind);
+                0.
+            }
+            None => {
+                self.coins += 1;
+                config.coin_value
+            }
+        }
+    }
+    // Timers run in real time, so slow motion doesn't stretch itself
+    pub fn update(&mut self, dt: f32) {
+        for kind in PickupKind::ALL {
+            if let Some(timer) = self.timer(kind) {
+                *timer = (*timer - dt).max(0.);
+            }
+        }
+    }
+    pub fn remaining(&self, kind: PickupKind) -> f32 {
+        match kind {
+            PickupKind::Coin       => 0.,
+            PickupKind::Shield     => self.shield,
+            PickupKind::SlowMotion => self.slow_motion,
+            PickupKind::DoubleJump => self.double_jump,
+            PickupKind::Magnet     => self.magnet,
+        }
+    }
+    pub fn active(&self, kind: PickupKind) -> bool {
+        self.remaining(kind) > 0.
+    }
+    // Uses up the shield, if there is one
+    pub fn absorb_hit(&mut self) -> bool {
+        if !self.active(PickupKind::Shield) {return false}
+        self.shield = 0.;
+        true
+    }
+    pub fn time_scale(&self, config: &PickupConfig) -> f32 {
+        if self.active(PickupKind::SlowMotion) {config.slow_motion_scale} else {1.}
+    }
+    pub fn coins(&self) -> u32 {
+        self.coins
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn power_ups_run_out(){
+        let config = PickupConfig::default();
+        let mut power_ups = PowerUps::new();
+        assert_eq!(power_ups.collect(PickupKind::Coin, &config), config.coin_value);
+        assert_eq!(power_ups.collect(PickupKind::SlowMotion, &config), 0.);
+        assert_eq!(power_ups.coins(), 1);
+        assert_eq!(power_ups.time_scale(&config), config.slow_motion_scale);
+        power_ups.update(config.slow_motion_duration + 0.1);
+        assert_eq!(power_ups.time_scale(&config), 1.);
+    }
+    #[test]
+    fn shield_absorbs_one_hit(){
+        let config = PickupConfig::default();
+        let mut power_ups = PowerUps::new();
+        assert!(!power_ups.absorb_hit());
+        power_ups.collect(PickupKind::Shield, &config);
+        assert!(power_ups.absorb_hit());
+        assert!(!power_ups.absorb_hit());
+    }
+}
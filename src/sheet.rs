@@ -0,0 +1,206 @@
+// Sprite sheets in Aseprite's JSON export format: one image holding every frame, plus
+// where each frame is, how long it shows, named tags and slices
+use serde::{Deserialize, Deserializer};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct SheetRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct SheetFrame {
+    pub frame: SheetRect,
+    // Milliseconds
+    #[serde(default = "default_duration")]
+    pub duration: u32,
+}
+
+fn default_duration() -> u32 {
+    100
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct SheetTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    #[serde(default)]
+    pub direction: Direction,
+    // How many times the tag plays, empty for forever
+    #[serde(default)]
+    pub repeat: String,
+}
+
+impl SheetTag {
+    pub fn ping_pong(&self) -> bool {
+        matches!(self.direction, Direction::Pingpong | Direction::PingpongReverse)
+    }
+    pub fn plays_once(&self) -> bool {
+        self.repeat == "1"
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct SliceKey {
+    // The slice applies from this frame on, until the next key
+    pub frame: usize,
+    pub bounds: SheetRect,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct SheetSlice {
+    pub name: String,
+    pub keys: Vec<SliceKey>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct SheetMeta {
+    // Relative to the JSON file
+    pub image: String,
+    #[serde(default, rename = "frameTags")]
+    pub frame_tags: Vec<SheetTag>,
+    #[serde(default)]
+    pub slices: Vec<SheetSlice>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct SpriteSheet {
+    #[serde(deserialize_with = "frames_in_order")]
+    pub frames: Vec<SheetFrame>,
+    pub meta: SheetMeta,
+}
+
+// Aseprite writes the frames either as an array or as an object keyed by file name ("hash").
+// Either way they're kept in the order they appear in the file.
+fn frames_in_order<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<SheetFrame>, D::Error> {
+    struct FramesVisitor;
+
+    impl<'de> Visitor<'de> for FramesVisitor {
+        type Value = Vec<SheetFrame>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array or an object of frames")
+        }
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some(frame) = seq.next_element()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some((_, frame)) = map.next_entry::<String, SheetFrame>()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+    }
+
+    deserializer.deserialize_any(FramesVisitor)
+}
+
+impl SpriteSheet {
+    pub fn from_json(src: &str) -> Result<SpriteSheet, serde_json::Error> {
+        serde_json::from_str(src)
+    }
+
+    // Path of the sheet's image, given the path of its JSON file
+    pub fn image_path(&self, json_path: &str) -> String {
+        match json_path.rfind('/') {
+            Some(slash) => format!("{}/{}", &json_path[..slash], self.meta.image),
+            None => self.meta.image.clone(),
+        }
+    }
+
+    // Frames a tag plays, in the order of its first pass. Going back and forth is up to the `Animation`.
+    pub fn tag_frames(&self, tag: &SheetTag) -> Vec<usize> {
+        let last = tag.to.min(self.frames.len().saturating_sub(1));
+        if tag.from > last {return Vec::new()}
+        let mut frames: Vec<usize> = (tag.from..=last).collect();
+        if matches!(tag.direction, Direction::Reverse | Direction::PingpongReverse) {
+            frames.reverse();
+        }
+        frames
+    }
+
+    // RGBA pixels of a frame, cut out of the sheet's pixels. `None` if the frame is outside the sheet.
+    pub fn frame_pixels(&self, frame: usize, (width, height): (u32, u32), pixels: &[u8]) -> Option<Vec<u8>> {
+        let rect = self.frames.get(frame)?.frame;
+        if rect.x + rect.w > width || rect.y + rect.h > height {return None}
+        let mut cut = Vec::with_capacity((rect.w * rect.h * 4) as usize);
+        for row in rect.y..rect.y + rect.h {
+            let from = ((row * width + rect.x) * 4) as usize;
+            cut.extend_from_slice(&pixels[from..from + (rect.w * 4) as usize]);
+        }
+        Some(cut)
+    }
+
+    // Bounds of a slice on a frame, relative to the frame's top left corner
+    pub fn slice(&self, name: &str, frame: usize) -> Option<SheetRect> {
+        let slice = self.meta.slices.iter().find(|slice| slice.name == name)?;
+        slice.keys.iter().filter(|key| key.frame <= frame).max_by_key(|key| key.frame).map(|key| key.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn reads_aseprite_hash_export(){
+        let sheet = SpriteSheet::from_json(r#"{
+            "frames": {
+                "bird 2.aseprite": { "frame": { "x": 20, "y": 0, "w": 10, "h": 8 }, "duration": 50 },
+                "bird 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 10, "h": 8 }, "duration": 100 },
+                "bird 1.aseprite": { "frame": { "x": 10, "y": 0, "w": 10, "h": 8 }, "duration": 100 }
+            },
+            "meta": {
+                "image": "bird.png",
+                "frameTags": [
+                    { "name": "fly", "from": 0, "to": 2, "direction": "pingpong" },
+                    { "name": "dive", "from": 2, "to": 2, "direction": "reverse", "repeat": "1" }
+                ],
+                "slices": [
+                    { "name": "beak", "keys": [
+                        { "frame": 0, "bounds": { "x": 8, "y": 2, "w": 2, "h": 1 } },
+                        { "frame": 2, "bounds": { "x": 8, "y": 5, "w": 2, "h": 1 } }
+                    ] }
+                ]
+            }
+        }"#).unwrap();
+        // File order, not name order
+        assert_eq!(sheet.frames[0].frame.x, 20);
+        assert_eq!(sheet.frames[0].duration, 50);
+        assert_eq!(sheet.image_path("/images/bird.json"), "/images/bird.png");
+        let (fly, dive) = (&sheet.meta.frame_tags[0], &sheet.meta.frame_tags[1]);
+        assert_eq!(sheet.tag_frames(fly), [0, 1, 2]);
+        assert!(fly.ping_pong() && !fly.plays_once());
+        assert_eq!(sheet.tag_frames(dive), [2]);
+        assert!(!dive.ping_pong() && dive.plays_once());
+        assert_eq!(sheet.slice("beak", 1).unwrap().y, 2);
+        assert_eq!(sheet.slice("beak", 2).unwrap().y, 5);
+        assert!(sheet.slice("tail", 0).is_none());
+
+        let pixels: Vec<u8> = (0..30 * 8).flat_map(|i| [i as u8; 4]).collect();
+        let dive = sheet.frame_pixels(0, (30, 8), &pixels).unwrap();
+        assert_eq!(dive.len(), 10 * 8 * 4);
+        assert_eq!(dive[0], 20);
+        assert_eq!(dive[10 * 4], 50);
+        assert!(sheet.frame_pixels(0, (25, 8), &pixels).is_none());
+    }
+}
@@ -7,11 +7,22 @@ pub const START_SCROLL_SPEED: f32 =   700.0;
 pub const MAX_SCROLL_SPEED  : f32 =  2100.0;
 pub const CACTUS_MIN_DELAY  : f32 =     0.7;
 pub const PTERO_SPEED       : f32 =   100.0;
+pub const ROCK_SPEED        : f32 =   250.0;
+pub const TUMBLEWEED_SPEED  : f32 =    60.0;
+pub const TUMBLEWEED_BOUNCE : f32 =   520.0;
+pub const TUMBLEWEED_GRAVITY: f32 = -2000.0;
+pub const OBSTACLE_POOL_CAP : usize =    32;
+pub const MAX_PARTICLES     : usize =   256;
+pub const LIVES             : u32 =       1;
+pub const INVULNERABILITY_TIME: f32 =   1.5;
+pub const CAMERA_ZOOM_OUT   : f32 =    0.08;
+pub const SCREEN_SHAKE      : f32 =    1.0;
 
 pub const NUM_OF_COLLIDERS  : usize = 2;
 pub const SHOW_COLLIDERS    : bool = false;
 pub const PAUSE_ENABLED     : bool = false;
 
+// Virtual resolution the game is laid out and drawn at. It's scaled to fit the window.
 pub const SCREEN: Screen2 = (1200.0, 600.0);
 pub const DESIRED_FPS: u32 = 60;
 
@@ -5,17 +5,25 @@ pub struct UIButton{
     active: bool,
     asset_tag: AssetTag,
     pub col: BoxCollider,
+    pub transform: SpriteTransform,
 }
 
 impl UIButton{
     pub fn new(assets: &Assets, image: AssetTag, pos: Vec2) -> Self{
-        let img = assets.get_image(image).unwrap();
-        Self{
+        let mut button = Self{
             pos,
             active: true,
             asset_tag: image,
-            col: BoxCollider::new(v2!(img.width() as f32 / 2., img.height() as f32 / 2.)),
-        }
+            col: BoxCollider::new(v2!(0., 0.)),
+            transform: SpriteTransform::default(),
+        };
+        button.fit_to_image(assets);
+        button
+    }
+    // Sizes the collider to the image, which can change with the theme
+    pub fn fit_to_image(&mut self, assets: &Assets){
+        let img = assets.get_image(self.asset_tag);
+        self.col = BoxCollider::new(v2!(img.width() as f32 / 2., img.height() as f32 / 2.));
     }
     pub fn activate(&mut self){
         self.active = true;
@@ -23,6 +31,10 @@ impl UIButton{
     pub fn deactivate(&mut self){
         self.active = false;
     }
+    // The collider grows and shrinks with the drawn image
+    pub fn contains_point(&self, point: Vec2) -> bool{
+        self.col.scaled(self.transform.scale).contains_point(self.pos, point)
+    }
 }
 
 impl Draw for UIButton{
@@ -30,13 +42,55 @@ impl Draw for UIButton{
         if !self.active{
             return Ok(())
         }
-        let img = assets.get_image(self.asset_tag).unwrap();
+        let img = assets.get_image(self.asset_tag);
         let mut pos = world_to_screen_coords(screen_size, self.pos);
         // Floor the coordinates to prevent blurring
         pos.x = pos.x.floor();
         pos.y = pos.y.floor();
         let draw_params = graphics::DrawParam::new()
-            .dest(pos - v2!(img.width() as f32 / 2., img.height() as f32 / 2.));
-        graphics::draw(ctx, img, draw_params)
+            .dest(pos)
+            .offset(v2!(0.5, 0.5));
+        graphics::draw(ctx, img, self.transform.apply(draw_params))
     }
-}
\ No newline at end of file
+}
+// Coin counter and the running power-ups with their time left, in the top left corner
+pub fn draw_power_ups(ctx: &mut Context, assets: &Assets, power_ups: &PowerUps, config: &PickupConfig) -> GameResult {
+    const COL: f32 = 83. / 255.;
+    let color = Color::new(COL, COL, COL, 1.0);
+    let mut pos = v2!(15., 15.);
+
+    let coin = assets.get_image(AssetTag::PICKUP_COIN);
+    graphics::draw(ctx, coin, graphics::DrawParam::new().dest(pos))?;
+    let coins = graphics::Text::new((format!("{:0>3}", power_ups.coins()), assets.font, 20.0));
+    graphics::draw(ctx, &coins, (pos + v2!(coin.width() as f32 + 8., 2.), 0.0, color))?;
+    pos.x += coin.width() as f32 + coins.width(ctx) + 24.;
+
+    for kind in PickupKind::ALL {
+        let duration = config.duration(kind);
+        if !power_ups.active(kind) || duration <= 0. {continue}
+        let img = assets.get_image(kind.asset_tag());
+        graphics::draw(ctx, img, graphics::DrawParam::new().dest(pos))?;
+        let left = (power_ups.remaining(kind) / duration).min(1.);
+        let bar = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(pos.x, pos.y + img.height() as f32 + 4., img.width() as f32 * left, 3.),
+            color,
+        )?;
+        graphics::draw(ctx, &bar, graphics::DrawParam::new())?;
+        pos.x += img.width() as f32 + 12.;
+    }
+    Ok(())
+}
+
+// Remaining lives under the score. Nothing is drawn in one-hit mode.
+pub fn draw_lives(ctx: &mut Context, assets: &Assets, health: &Health) -> GameResult {
+    if health.max_lives() <= 1 {return Ok(())}
+    let heart = assets.get_image(AssetTag::HEART);
+    let step = heart.width() as f32 + 6.;
+    for i in 0..health.lives() {
+        let pos = v2!(SCREEN.0 - 15. - step * (i + 1) as f32 + 6., 45.);
+        graphics::draw(ctx, heart, graphics::DrawParam::new().dest(pos))?;
+    }
+    Ok(())
+}
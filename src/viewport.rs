@@ -0,0 +1,78 @@
+// Fits the fixed virtual resolution (`SCREEN`) the game is drawn at into the window,
+// with bars around it where the aspect ratios don't match
+use crate::prelude::*;
+use ggez::graphics::{Canvas, DrawParam};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    window_size: Screen2,
+    // Only scale by whole numbers, so every pixel of the art stays the same size.
+    // Windows smaller than the virtual resolution still get scaled down to fit.
+    integer_scaling: bool,
+}
+
+impl Viewport {
+    pub fn new(window_size: Screen2, integer_scaling: bool) -> Viewport {
+        Viewport {
+            window_size,
+            integer_scaling,
+        }
+    }
+    pub fn resize(&mut self, window_size: Screen2) {
+        self.window_size = window_size;
+    }
+    pub fn set_integer_scaling(&mut self, integer_scaling: bool) {
+        self.integer_scaling = integer_scaling;
+    }
+    pub fn scale(&self) -> f32 {
+        let fit = (self.window_size.0 / SCREEN.0).min(self.window_size.1 / SCREEN.1);
+        if self.integer_scaling && fit >= 1. {fit.floor()} else {fit}
+    }
+    // Top left corner of the game inside the window
+    pub fn offset(&self) -> Vec2 {
+        let scale = self.scale();
+        v2!(self.window_size.0 - SCREEN.0 * scale, self.window_size.1 - SCREEN.1 * scale) / 2.
+    }
+    pub fn window_to_virtual(&self, point: Vec2) -> Vec2 {
+        (point - self.offset()) / self.scale()
+    }
+    pub fn draw_param(&self) -> DrawParam {
+        let scale = self.scale();
+        DrawParam::new().dest(self.offset()).scale(v2!(scale, scale))
+    }
+
+    // A canvas at the virtual resolution to draw the frame into
+    pub fn canvas(ctx: &mut Context) -> GameResult<Canvas> {
+        let format = graphics::get_window_color_format(ctx);
+        let mut canvas = Canvas::new(ctx, SCREEN.0 as u16, SCREEN.1 as u16, conf::NumSamples::One, format)?;
+        canvas.set_filter(graphics::FilterMode::Nearest);
+        Ok(canvas)
+    }
+    // Switches drawing over to the window itself, with the bars cleared to black
+    pub fn begin_window(&self, ctx: &mut Context) -> GameResult {
+        graphics::set_canvas(ctx, None);
+        graphics::set_screen_coordinates(ctx, graphics::Rect::new(0., 0., self.window_size.0, self.window_size.1))?;
+        graphics::clear(ctx, Color::BLACK);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn letterboxing(){
+        let mut viewport = Viewport::new((1920., 1080.), true);
+        assert_eq!(viewport.scale(), 1.);
+        assert_eq!(viewport.offset(), v2!(360., 240.));
+        viewport.set_integer_scaling(false);
+        assert_eq!(viewport.scale(), 1.6);
+        assert_eq!(viewport.offset(), v2!(0., 60.));
+        assert_eq!(viewport.window_to_virtual(v2!(960., 540.)), v2!(600., 300.));
+
+        // Too small for integer scaling
+        viewport = Viewport::new((600., 600.), true);
+        assert_eq!(viewport.scale(), 0.5);
+        assert_eq!(viewport.offset(), v2!(0., 150.));
+    }
+}